@@ -0,0 +1,96 @@
+use {
+    gtk::{
+        gdk_pixbuf::{InterpType, Pixbuf, PixbufAnimationIter},
+        glib,
+    },
+    std::{
+        cell::Cell,
+        time::{Duration, SystemTime},
+    },
+};
+
+/// An animated image playing on the current page
+pub(crate) struct Animation {
+    picture: glib::WeakRef<gtk::Picture>,
+    frames: PixbufAnimationIter,
+    /// The animation keeps its own clock so that pausing does not cause frames
+    /// to be skipped when playback resumes
+    clock: Cell<SystemTime>,
+    pub playing: Cell<bool>,
+    /// Whether a timer is pending to show the next frame, so that resuming
+    /// playback does not start a second one
+    pub scheduled: Cell<bool>,
+    /// The width in pixels which frames are scaled to, as static images are
+    /// scaled to the width of the view
+    width: Cell<i32>,
+}
+
+impl Animation {
+    pub fn new(
+        picture: &gtk::Picture,
+        frames: PixbufAnimationIter,
+        clock: SystemTime,
+        playing: bool,
+        width: i32,
+    ) -> Self {
+        let animation = Self {
+            picture: picture.downgrade(),
+            frames,
+            clock: Cell::new(clock),
+            playing: Cell::new(playing),
+            scheduled: Cell::new(false),
+            width: Cell::new(width),
+        };
+        animation.show_frame();
+        animation
+    }
+
+    /// Gets how long the current frame is displayed for, or `None` if the
+    /// animation has no further frames
+    pub fn delay(&self) -> Option<Duration> {
+        self.frames.delay_time()
+    }
+
+    /// Moves the animation on by `delay` if it is playing. Returns `false` if
+    /// it is paused or the picture has been removed from the page, in which
+    /// case no further frame should be scheduled.
+    pub fn advance(&self, delay: Duration) -> bool {
+        if self.picture.upgrade().is_none() || !self.playing.get() {
+            return false;
+        }
+        let clock = self.clock.get() + delay;
+        self.clock.set(clock);
+        if self.frames.advance(clock) {
+            self.show_frame();
+        }
+        true
+    }
+
+    /// Changes the width which frames are scaled to, redrawing the current
+    /// frame without interrupting playback
+    pub fn set_width(&self, width: i32) {
+        if self.width.replace(width) != width {
+            self.show_frame();
+        }
+    }
+
+    /// Displays the current frame, scaled to the width of the animation
+    fn show_frame(&self) {
+        if let Some(picture) = self.picture.upgrade() {
+            let frame = scale_to_width(&self.frames.pixbuf(), self.width.get());
+            picture.set_pixbuf(Some(&frame));
+        }
+    }
+}
+
+/// Scales an image to the given width, keeping its aspect ratio
+pub(crate) fn scale_to_width(pixbuf: &Pixbuf, width: i32) -> Pixbuf {
+    if width <= 0 || width == pixbuf.width() {
+        return pixbuf.clone();
+    }
+    let height = i64::from(pixbuf.height()) * i64::from(width) / i64::from(pixbuf.width());
+    let height = i32::try_from(height).unwrap_or(i32::MAX).max(1);
+    pixbuf
+        .scale_simple(width, height, InterpType::Bilinear)
+        .unwrap_or_else(|| pixbuf.clone())
+}
@@ -0,0 +1,35 @@
+/// Returns `true` if the given language tag, eg "ar" or "he-IL", names a
+/// language which is written right to left
+pub(crate) fn is_rtl_language(lang: &str) -> bool {
+    let primary = lang.split(['-', '_']).next().unwrap_or_default();
+    [
+        "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ks", "ku", "ps", "sd", "syr", "ug", "ur", "yi",
+    ]
+    .iter()
+    .any(|l| primary.eq_ignore_ascii_case(l))
+}
+
+/// Finds the base direction of a line of text from its first strongly
+/// directional character, as the Unicode bidirectional algorithm does.
+/// Returns `None` if the text has no such character, eg if it contains only
+/// numbers, punctuation or emoji.
+pub(crate) fn base_direction(text: &str) -> Option<gtk::TextDirection> {
+    text.chars().find_map(|c| {
+        if is_rtl_char(c) {
+            Some(gtk::TextDirection::Rtl)
+        } else if c.is_alphabetic() {
+            Some(gtk::TextDirection::Ltr)
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns `true` for characters in the Hebrew, Arabic, Syriac, Thaana, N'Ko
+/// and other right to left blocks
+fn is_rtl_char(c: char) -> bool {
+    matches!(
+        u32::from(c),
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x1_0800..=0x1_0FFF | 0x1_E800..=0x1_EFFF
+    ) && c.is_alphabetic()
+}
@@ -0,0 +1,504 @@
+use {
+    crate::savefile,
+    std::{
+        collections::HashMap,
+        fmt::Write,
+        fs, io,
+        path::Path,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// A bookmarked page
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bookmark {
+    /// The url of the bookmarked page
+    pub url: String,
+    /// The title of the bookmark
+    pub title: String,
+    /// Any tags applied to the bookmark
+    pub tags: Vec<String>,
+    /// The folder containing this bookmark. Nested folders are separated by
+    /// a '/' character.
+    pub folder: Option<String>,
+    /// When the bookmark was created, in seconds since the Unix epoch. This is
+    /// set to the current time when a bookmark without one is added.
+    pub created: Option<u64>,
+}
+
+#[derive(Default)]
+pub(crate) struct Bookmarks {
+    pub items: Vec<Bookmark>,
+    /// When each folder was created, in seconds since the Unix epoch, keyed by
+    /// the full path of the folder
+    folders_created: HashMap<String, u64>,
+}
+
+impl Bookmarks {
+    /// Adds a bookmark, replacing any existing bookmark with the same url.
+    /// The creation time of a replaced bookmark is kept.
+    pub fn add(&mut self, mut bookmark: Bookmark) {
+        if let Some(b) = self.items.iter_mut().find(|b| b.url == bookmark.url) {
+            bookmark.created = bookmark.created.or(b.created);
+            *b = bookmark;
+        } else {
+            self.items.push(bookmark);
+        }
+        self.stamp();
+    }
+
+    /// Removes the bookmark for `url`, returning `false` if there was none
+    pub fn remove(&mut self, url: &str) -> bool {
+        let len = self.items.len();
+        self.items.retain(|b| b.url != url);
+        len != self.items.len()
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.items.iter().any(|b| b.url == url)
+    }
+
+    /// Merges in a list of bookmarks, returning the number added. Bookmarks
+    /// for urls which are already present are skipped.
+    pub fn merge(&mut self, bookmarks: Vec<Bookmark>) -> usize {
+        let mut added = 0;
+        for bookmark in bookmarks {
+            if !self.contains(&bookmark.url) {
+                self.items.push(bookmark);
+                added += 1;
+            }
+        }
+        self.stamp();
+        added
+    }
+
+    /// Gives the current time as the creation time of any bookmarks and
+    /// folders which do not have one yet
+    fn stamp(&mut self) {
+        let now = now();
+        for bookmark in &mut self.items {
+            bookmark.created.get_or_insert(now);
+            let Some(folder) = &bookmark.folder else {
+                continue;
+            };
+            let mut path = String::new();
+            for name in folder.split('/') {
+                if !path.is_empty() {
+                    path.push('/');
+                }
+                path.push_str(name);
+                self.folders_created.entry(path.clone()).or_insert(now);
+            }
+        }
+    }
+
+    /// Reads bookmarks from a file in Lagrange format. A missing file is not
+    /// an error, and results in an empty list.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(data) => {
+                let (items, folders_created) = parse_lagrange(&data);
+                Ok(Self {
+                    items,
+                    folders_created,
+                })
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes bookmarks to a file in Lagrange format
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        savefile::write(path, &self.to_lagrange())
+    }
+
+    fn folders(&self) -> Vec<&str> {
+        let mut folders = self
+            .items
+            .iter()
+            .filter_map(|b| b.folder.as_deref())
+            .collect::<Vec<_>>();
+        folders.sort_unstable();
+        folders.dedup();
+        folders
+    }
+
+    /// Generates a gemtext bookmarks page. Bookmarks without a folder are
+    /// listed first, followed by a section for each folder.
+    pub fn to_gemtext(&self) -> String {
+        let mut page = String::from("# Bookmarks\n\n");
+        for b in self.items.iter().filter(|b| b.folder.is_none()) {
+            write_gemtext_link(&mut page, b);
+        }
+        for folder in self.folders() {
+            let _ = writeln!(page, "\n## {folder}");
+            for b in self
+                .items
+                .iter()
+                .filter(|b| b.folder.as_deref() == Some(folder))
+            {
+                write_gemtext_link(&mut page, b);
+            }
+        }
+        page
+    }
+
+    /// Generates a bookmarks file in the format used by the Lagrange browser
+    pub fn to_lagrange(&self) -> String {
+        let now = now();
+        let mut out = String::new();
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        let mut next = 1;
+        for folder in self.folders() {
+            let mut path = String::new();
+            for name in folder.split('/') {
+                let parent = ids.get(&path).copied();
+                if !path.is_empty() {
+                    path.push('/');
+                }
+                path.push_str(name);
+                if ids.contains_key(&path) {
+                    continue;
+                }
+                let _ = writeln!(out, "[{next}]");
+                let created = self.folders_created.get(&path).copied();
+                let _ = writeln!(out, "title = \"{}\"", escape(name));
+                let _ = writeln!(out, "created = {}", created.unwrap_or(now));
+                if let Some(parent) = parent {
+                    let _ = writeln!(out, "parent = {parent}");
+                }
+                out.push('\n');
+                ids.insert(path.clone(), next);
+                next += 1;
+            }
+        }
+        for b in &self.items {
+            let _ = writeln!(out, "[{next}]");
+            let _ = writeln!(out, "url = \"{}\"", escape(&b.url));
+            let _ = writeln!(out, "title = \"{}\"", escape(&b.title));
+            if !b.tags.is_empty() {
+                let _ = writeln!(out, "tags = \"{}\"", escape(&b.tags.join(" ")));
+            }
+            let _ = writeln!(out, "created = {}", b.created.unwrap_or(now));
+            if let Some(parent) = b.folder.as_ref().and_then(|f| ids.get(f)) {
+                let _ = writeln!(out, "parent = {parent}");
+            }
+            out.push('\n');
+            next += 1;
+        }
+        out
+    }
+}
+
+fn write_gemtext_link(page: &mut String, bookmark: &Bookmark) {
+    if bookmark.title.is_empty() {
+        let _ = writeln!(page, "=> {}", bookmark.url);
+    } else {
+        let _ = writeln!(page, "=> {} {}", bookmark.url, bookmark.title);
+    }
+}
+
+/// Parses a gemtext bookmarks page. Each link line becomes a bookmark, placed
+/// in a folder named after the closest preceding level two heading.
+pub(crate) fn from_gemtext(data: &str) -> Vec<Bookmark> {
+    let mut folder = None;
+    let mut bookmarks = vec![];
+    for line in data.lines() {
+        if let Some(link) = line.strip_prefix("=>") {
+            let link = link.trim();
+            if link.is_empty() {
+                continue;
+            }
+            let (url, title) = link
+                .split_once(char::is_whitespace)
+                .map_or((link, ""), |(u, t)| (u, t.trim()));
+            bookmarks.push(Bookmark {
+                url: url.to_string(),
+                title: title.to_string(),
+                tags: vec![],
+                folder: folder.clone(),
+                created: None,
+            });
+        } else if let Some(heading) = line.strip_prefix("##") {
+            if !heading.starts_with('#') {
+                let heading = heading.trim();
+                folder = if heading.is_empty() {
+                    None
+                } else {
+                    Some(heading.to_string())
+                };
+            }
+        }
+    }
+    bookmarks
+}
+
+/// Parses a Lagrange bookmarks file. Each numbered section is either a
+/// bookmark, if it has a url, or a folder. Folders are nested through their
+/// `parent` key.
+pub(crate) fn from_lagrange(data: &str) -> Vec<Bookmark> {
+    parse_lagrange(data).0
+}
+
+/// Parses a Lagrange bookmarks file into its bookmarks along with the
+/// creation time of each folder, keyed by the full path of the folder
+fn parse_lagrange(data: &str) -> (Vec<Bookmark>, HashMap<String, u64>) {
+    let mut sections: Vec<(String, HashMap<String, String>)> = vec![];
+    for line in data.lines() {
+        let line = line.trim();
+        if let Some(id) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((id.to_string(), HashMap::new()));
+        } else if let Some((key, value)) = line.split_once('=') {
+            if let Some((_, section)) = sections.last_mut() {
+                section.insert(key.trim().to_string(), unescape(value.trim()));
+            }
+        }
+    }
+    // Lagrange writes folders with an empty url
+    let is_folder =
+        |s: &HashMap<String, String>| s.get("url").map_or("", String::as_str).is_empty();
+    let created = |s: &HashMap<String, String>| s.get("created").and_then(|c| c.parse().ok());
+    let folders = sections
+        .iter()
+        .filter(|(_, s)| is_folder(s))
+        .map(|(id, s)| {
+            let title = s.get("title").cloned().unwrap_or_default();
+            (id.as_str(), (title, s.get("parent").map(String::as_str)))
+        })
+        .collect::<HashMap<_, _>>();
+    let folders_created = sections
+        .iter()
+        .filter(|(_, s)| is_folder(s))
+        .filter_map(|(id, s)| Some((folder_path(&folders, Some(id.as_str()))?, created(s)?)))
+        .collect();
+    let bookmarks = sections
+        .iter()
+        .filter(|(_, s)| !is_folder(s))
+        .filter_map(|(_, s)| {
+            let url = s.get("url")?;
+            Some(Bookmark {
+                url: url.clone(),
+                title: s.get("title").cloned().unwrap_or_default(),
+                tags: s
+                    .get("tags")
+                    .map(|t| t.split_whitespace().map(String::from).collect())
+                    .unwrap_or_default(),
+                folder: folder_path(&folders, s.get("parent").map(String::as_str)),
+                created: created(s),
+            })
+        })
+        .collect();
+    (bookmarks, folders_created)
+}
+
+/// Builds the full path of a Lagrange folder by walking up its parents
+fn folder_path<'a>(
+    folders: &HashMap<&'a str, (String, Option<&'a str>)>,
+    mut parent: Option<&'a str>,
+) -> Option<String> {
+    let mut path: Vec<&str> = vec![];
+    while let Some((title, next)) = parent.and_then(|p| folders.get(p)) {
+        // Guard against cycles in malformed files
+        if path.len() > folders.len() {
+            break;
+        }
+        path.insert(0, title);
+        parent = *next;
+    }
+    if path.is_empty() {
+        None
+    } else {
+        Some(path.join("/"))
+    }
+}
+
+/// Gets the current time in seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let Some(value) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) else {
+        return value.to_string();
+    };
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(c) => out.push(c),
+                None => {}
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAGRANGE: &str = r#"[1]
+url = ""
+title = "Gemini"
+created = 1600000000
+
+[2]
+title = "Software \"tools\""
+created = 1600000100
+parent = 1
+
+[3]
+url = "gemini://example.org/"
+title = "Example\nCapsule"
+tags = "home reading"
+created = 1600000200
+parent = 2
+
+[4]
+url = "gemini://example.org/unfiled.gmi"
+title = "Unfiled"
+created = 1600000300
+"#;
+
+    #[test]
+    fn lagrange() {
+        let (bookmarks, folders) = parse_lagrange(LAGRANGE);
+        assert_eq!(
+            bookmarks,
+            vec![
+                Bookmark {
+                    url: String::from("gemini://example.org/"),
+                    title: String::from("Example\nCapsule"),
+                    tags: vec![String::from("home"), String::from("reading")],
+                    folder: Some(String::from("Gemini/Software \"tools\"")),
+                    created: Some(1_600_000_200),
+                },
+                Bookmark {
+                    url: String::from("gemini://example.org/unfiled.gmi"),
+                    title: String::from("Unfiled"),
+                    tags: vec![],
+                    folder: None,
+                    created: Some(1_600_000_300),
+                },
+            ]
+        );
+        assert_eq!(folders.get("Gemini"), Some(&1_600_000_000));
+        assert_eq!(
+            folders.get("Gemini/Software \"tools\""),
+            Some(&1_600_000_100)
+        );
+    }
+
+    #[test]
+    fn lagrange_round_trip() {
+        let (items, folders_created) = parse_lagrange(LAGRANGE);
+        let bookmarks = Bookmarks {
+            items,
+            folders_created,
+        };
+        let (items, folders_created) = parse_lagrange(&bookmarks.to_lagrange());
+        assert_eq!(items, bookmarks.items);
+        assert_eq!(folders_created, bookmarks.folders_created);
+    }
+
+    #[test]
+    fn added_bookmarks_are_stamped() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.add(Bookmark {
+            url: String::from("gemini://example.org/"),
+            folder: Some(String::from("a/b")),
+            ..Bookmark::default()
+        });
+        let created = bookmarks.items[0].created;
+        assert!(created.is_some());
+        assert!(bookmarks.folders_created.contains_key("a"));
+        assert!(bookmarks.folders_created.contains_key("a/b"));
+        bookmarks.add(Bookmark {
+            url: String::from("gemini://example.org/"),
+            title: String::from("Renamed"),
+            ..Bookmark::default()
+        });
+        assert_eq!(bookmarks.items.len(), 1);
+        assert_eq!(bookmarks.items[0].created, created);
+    }
+
+    #[test]
+    fn folder_cycles() {
+        let folders = HashMap::from([
+            ("1", (String::from("a"), Some("2"))),
+            ("2", (String::from("b"), Some("1"))),
+        ]);
+        let path = folder_path(&folders, Some("1")).unwrap();
+        assert!(path.split('/').count() <= folders.len() + 1);
+        assert_eq!(folder_path(&folders, Some("3")), None);
+        assert_eq!(folder_path(&folders, None), None);
+    }
+
+    #[test]
+    fn gemtext() {
+        let page = "# Bookmarks\n\
+            => gemini://example.org/ Example\n\
+            =>gemini://example.org/bare.gmi\n\
+            =>\n\
+            ## Reading\n\
+            => gemini://example.org/book.gmi   A book \n\
+            ### Not a folder\n\
+            => gemini://example.org/other.gmi Other\n\
+            ##\n\
+            => gemini://example.org/last.gmi Last\n";
+        let bookmarks = from_gemtext(page);
+        let summary = bookmarks
+            .iter()
+            .map(|b| (b.url.as_str(), b.title.as_str(), b.folder.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("gemini://example.org/", "Example", None),
+                ("gemini://example.org/bare.gmi", "", None),
+                ("gemini://example.org/book.gmi", "A book", Some("Reading")),
+                ("gemini://example.org/other.gmi", "Other", Some("Reading")),
+                ("gemini://example.org/last.gmi", "Last", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn gemtext_round_trip() {
+        let (items, _) = parse_lagrange(LAGRANGE);
+        let bookmarks = Bookmarks {
+            items,
+            ..Bookmarks::default()
+        };
+        let parsed = from_gemtext(&bookmarks.to_gemtext());
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].url, "gemini://example.org/unfiled.gmi");
+        assert_eq!(
+            parsed[1].folder.as_deref(),
+            Some("Gemini/Software \"tools\"")
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let text = "a \"quoted\" \\path\\\nnext line";
+        assert_eq!(unescape(&format!("\"{}\"", escape(text))), text);
+        assert_eq!(unescape("\"tab\\there\""), "tab\there");
+        assert_eq!(unescape("1600000000"), "1600000000");
+    }
+}
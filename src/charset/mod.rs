@@ -0,0 +1,151 @@
+use {
+    chardetng::EncodingDetector,
+    encoding_rs::{Encoding, UTF_8},
+};
+
+/// Splits a mime type into the bare type and the value of its `charset`
+/// parameter, if any, eg "text/plain; charset=iso-8859-1"
+pub(crate) fn split_mime(mime: &str) -> (&str, Option<&str>) {
+    let bare = mime.split(';').next().unwrap_or("").trim();
+    (bare, mime_param(mime, "charset"))
+}
+
+/// Gets the value of the named parameter of a mime type. Parameter names are
+/// matched case insensitively.
+pub(crate) fn mime_param<'a>(mime: &'a str, name: &str) -> Option<&'a str> {
+    mime.split(';').skip(1).find_map(|p| {
+        let (key, value) = p.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case(name) {
+            Some(value.trim().trim_matches('"'))
+        } else {
+            None
+        }
+    })
+}
+
+/// Chooses the label to decode a page with. A non-empty `forced` label, from
+/// the "encoding-override" property, takes precedence over the charset
+/// declared by the server.
+pub(crate) fn label<'a>(forced: &'a str, declared: Option<&'a str>) -> Option<&'a str> {
+    if forced.is_empty() {
+        declared
+    } else {
+        Some(forced)
+    }
+}
+
+/// Decodes text into a string, returning it along with the name of the
+/// encoding which was used. The encoding is taken from `label` if it names a
+/// known encoding, otherwise valid UTF-8 is assumed to be UTF-8 and anything
+/// else is sniffed. A byte order mark overrides all of these.
+pub(crate) fn decode(bytes: &[u8], label: Option<&str>) -> (String, &'static str) {
+    let encoding = label
+        .and_then(|l| Encoding::for_label(l.as_bytes()))
+        .unwrap_or_else(|| {
+            if std::str::from_utf8(bytes).is_ok() {
+                UTF_8
+            } else {
+                let mut detector = EncodingDetector::new();
+                detector.feed(bytes, true);
+                detector.guess(None, true)
+            }
+        });
+    let (text, encoding, _) = encoding.decode(bytes);
+    (text.into_owned(), encoding.name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "café Noël" in Latin-1
+    const LATIN1: &[u8] = b"caf\xe9 No\xebl";
+    // "日本語のテキストです。" in Shift_JIS
+    const SHIFT_JIS: &[u8] =
+        b"\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x83\x65\x83\x4c\x83\x58\x83\x67\x82\xc5\x82\xb7\x81\x42";
+
+    #[test]
+    fn mime_types() {
+        assert_eq!(split_mime("text/gemini"), ("text/gemini", None));
+        assert_eq!(
+            split_mime("text/plain; charset=iso-8859-1"),
+            ("text/plain", Some("iso-8859-1"))
+        );
+        assert_eq!(
+            split_mime(" text/gemini ;lang=en; charset=utf-8 "),
+            ("text/gemini", Some("utf-8"))
+        );
+        assert_eq!(split_mime(""), ("", None));
+    }
+
+    #[test]
+    fn mime_params() {
+        let mime = "text/gemini; Lang=\"en\"; CHARSET = Shift_JIS";
+        assert_eq!(mime_param(mime, "lang"), Some("en"));
+        assert_eq!(mime_param(mime, "charset"), Some("Shift_JIS"));
+        assert_eq!(mime_param(mime, "format"), None);
+        assert_eq!(mime_param("text/plain; charset", "charset"), None);
+        // the bare type is not a parameter
+        assert_eq!(mime_param("charset=utf-8", "charset"), None);
+    }
+
+    #[test]
+    fn declared_charsets() {
+        assert_eq!(
+            decode(LATIN1, Some("iso-8859-1")),
+            (String::from("café Noël"), "windows-1252")
+        );
+        assert_eq!(
+            decode(SHIFT_JIS, Some("shift_jis")),
+            (String::from("日本語のテキストです。"), "Shift_JIS")
+        );
+        assert_eq!(
+            decode("café".as_bytes(), Some("UTF-8")),
+            (String::from("café"), "UTF-8")
+        );
+    }
+
+    #[test]
+    fn sniffed_charsets() {
+        assert_eq!(
+            decode("日本語".as_bytes(), None),
+            (String::from("日本語"), "UTF-8")
+        );
+        assert_eq!(
+            decode(LATIN1, None),
+            (String::from("café Noël"), "windows-1252")
+        );
+        assert_eq!(
+            decode(SHIFT_JIS, None),
+            (String::from("日本語のテキストです。"), "Shift_JIS")
+        );
+        // an unknown label is ignored
+        assert_eq!(
+            decode(SHIFT_JIS, Some("x-unknown")),
+            (String::from("日本語のテキストです。"), "Shift_JIS")
+        );
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        assert_eq!(
+            decode(b"\xef\xbb\xbfcaf\xc3\xa9", Some("iso-8859-1")),
+            (String::from("café"), "UTF-8")
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        assert_eq!(label("", None), None);
+        assert_eq!(label("", Some("utf-8")), Some("utf-8"));
+        assert_eq!(label("shift_jis", None), Some("shift_jis"));
+        assert_eq!(label("iso-8859-1", Some("utf-8")), Some("iso-8859-1"));
+        // a page wrongly declared as UTF-8 is readable once overridden
+        let (text, _) = decode(LATIN1, label("", Some("utf-8")));
+        assert_eq!(text, "caf\u{fffd} No\u{fffd}l");
+        assert_eq!(
+            decode(LATIN1, label("iso-8859-1", Some("utf-8"))),
+            (String::from("café Noël"), "windows-1252")
+        );
+    }
+}
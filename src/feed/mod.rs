@@ -0,0 +1,474 @@
+use {
+    crate::{charset, request},
+    bucky::prelude::{Content, Response},
+    quick_xml::{
+        events::{BytesStart, Event},
+        Reader,
+    },
+    std::fmt::Write,
+    url::Url,
+};
+
+mod subscriptions;
+pub use subscriptions::Subscription;
+pub(crate) use subscriptions::Subscriptions;
+
+/// A single entry in a gemlog feed
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeedEntry {
+    /// The absolute url of the entry
+    pub url: String,
+    /// The title of the entry
+    pub title: String,
+    /// The publication date, in `YYYY-MM-DD` form when it could be determined
+    pub date: String,
+}
+
+/// A gemlog feed, parsed from either a gemtext page following the
+/// [gemfeed](gemini://gemini.circumlunar.space/docs/companion/subscription.gmi)
+/// conventions or from an Atom or RSS document
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Feed {
+    /// The url which the feed was retrieved from
+    pub url: String,
+    /// The feed title
+    pub title: Option<String>,
+    /// The feed subtitle, if any
+    pub subtitle: Option<String>,
+    /// Feed entries, sorted newest first
+    pub entries: Vec<FeedEntry>,
+}
+
+/// Returns `true` if the given mime type may contain an Atom or RSS feed
+pub(crate) fn is_xml_mime(mime: &str) -> bool {
+    matches!(
+        mime,
+        "application/atom+xml" | "application/rss+xml" | "application/xml" | "text/xml"
+    )
+}
+
+/// Retrieves and parses the feed at `url`. This function blocks, and should be
+/// called from a background thread.
+pub(crate) fn fetch(url: &str, socks: Option<&str>) -> Result<Feed, String> {
+    let url = Url::parse(url).map_err(|e| format!("{e:?}"))?;
+    let response = match url.scheme() {
+        "gemini" => request::gemini(url.clone(), socks),
+        "spartan" => request::spartan(url.clone(), socks),
+        "gopher" => request::gopher(&url, socks),
+        s => return Err(format!("unsupported-scheme: {s}")),
+    };
+    match response {
+        Response::Success(content) => Feed::from_content(&content, url.as_str())
+            .ok_or_else(|| String::from("no feed found")),
+        Response::Error(e) => Err(e),
+        _ => Err(String::from("unexpected response")),
+    }
+}
+
+impl Feed {
+    /// Parses the content of a response as either a gemfeed or an Atom or RSS
+    /// feed, depending on the mime type
+    #[must_use]
+    pub fn from_content(content: &Content, url: &str) -> Option<Self> {
+        let url = content.url.as_deref().unwrap_or(url);
+        let (mime, charset) = charset::split_mime(&content.mime);
+        let (text, _) = charset::decode(&content.bytes, charset);
+        if mime == "text/gemini" {
+            Self::from_gemtext(&text, url)
+        } else {
+            Self::from_xml(&text, url).or_else(|| Self::from_gemtext(&text, url))
+        }
+    }
+
+    /// Parses a gemtext page as a gemfeed. A page is considered a feed if most
+    /// of its link lines have a label beginning with an ISO 8601 date, so that
+    /// a page merely linking to one dated post is not mistaken for a feed. The feed title is the first level one heading and the subtitle is
+    /// a level two heading immediately following it.
+    #[must_use]
+    pub fn from_gemtext(data: &str, url: &str) -> Option<Self> {
+        let base = Url::parse(url).ok();
+        let mut feed = Self {
+            url: url.to_string(),
+            ..Self::default()
+        };
+        let mut after_title = false;
+        let mut links = 0;
+        for line in data.lines() {
+            if let Some(link) = line.strip_prefix("=>") {
+                after_title = false;
+                links += 1;
+                let link = link.trim();
+                let Some((href, display)) = link.split_once(char::is_whitespace) else {
+                    continue;
+                };
+                let display = display.trim_start();
+                if let Some(date) = display.get(..10).filter(|d| is_iso_date(d)) {
+                    let title = display[10..]
+                        .trim_start()
+                        .trim_start_matches(['-', '–', '—', ':'])
+                        .trim();
+                    feed.entries.push(FeedEntry {
+                        url: resolve(base.as_ref(), href),
+                        title: if title.is_empty() {
+                            href.to_string()
+                        } else {
+                            title.to_string()
+                        },
+                        date: date.to_string(),
+                    });
+                }
+            } else if let Some(h2) = line.strip_prefix("##") {
+                if after_title && !h2.starts_with('#') && feed.subtitle.is_none() {
+                    feed.subtitle = Some(h2.trim().to_string());
+                }
+                after_title = false;
+            } else if let Some(h1) = line.strip_prefix('#') {
+                if feed.title.is_none() {
+                    feed.title = Some(h1.trim().to_string());
+                    after_title = true;
+                }
+            } else if !line.trim().is_empty() {
+                after_title = false;
+            }
+        }
+        if feed.entries.is_empty() || feed.entries.len() * 2 <= links {
+            None
+        } else {
+            feed.sort();
+            Some(feed)
+        }
+    }
+
+    /// Parses an Atom or RSS document
+    #[must_use]
+    pub fn from_xml(data: &str, url: &str) -> Option<Self> {
+        let base = Url::parse(url).ok();
+        let mut reader = Reader::from_str(data);
+        reader.trim_text(true);
+        let mut feed = Self {
+            url: url.to_string(),
+            ..Self::default()
+        };
+        let mut is_feed = false;
+        let mut entry: Option<FeedEntry> = None;
+        let mut path: Vec<String> = vec![];
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e)) => {
+                    let name = local_name(&e);
+                    match name.as_str() {
+                        "feed" | "rss" | "RDF" => is_feed = true,
+                        "entry" | "item" => entry = Some(FeedEntry::default()),
+                        "link" => {
+                            if let (Some(entry), Some(href)) = (entry.as_mut(), atom_href(&e)) {
+                                entry.url = resolve(base.as_ref(), &href);
+                            }
+                        }
+                        _ => {}
+                    }
+                    path.push(name);
+                }
+                Ok(Event::Empty(e)) if local_name(&e) == "link" => {
+                    if let (Some(entry), Some(href)) = (entry.as_mut(), atom_href(&e)) {
+                        entry.url = resolve(base.as_ref(), &href);
+                    }
+                }
+                Ok(Event::Text(e)) => {
+                    if let Ok(text) = e.unescape() {
+                        feed.set_field(&path, entry.as_mut(), &text, base.as_ref());
+                    }
+                }
+                Ok(Event::CData(e)) => {
+                    let text = String::from_utf8_lossy(&e.into_inner()).to_string();
+                    feed.set_field(&path, entry.as_mut(), &text, base.as_ref());
+                }
+                Ok(Event::End(_)) => {
+                    if let Some(name) = path.pop() {
+                        if name == "entry" || name == "item" {
+                            if let Some(e) = entry.take() {
+                                if !e.url.is_empty() {
+                                    feed.entries.push(e);
+                                }
+                            }
+                        }
+                    }
+                }
+                Ok(Event::Eof) => break,
+                Err(_) => return None,
+                _ => {}
+            }
+        }
+        if is_feed {
+            feed.sort();
+            Some(feed)
+        } else {
+            None
+        }
+    }
+
+    fn set_field(
+        &mut self,
+        path: &[String],
+        entry: Option<&mut FeedEntry>,
+        text: &str,
+        base: Option<&Url>,
+    ) {
+        let Some(name) = path.last() else {
+            return;
+        };
+        if let Some(entry) = entry {
+            match name.as_str() {
+                "title" => entry.title = text.trim().to_string(),
+                "link" => entry.url = resolve(base, text.trim()),
+                "updated" | "date" => entry.date = normalize_date(text),
+                "published" | "pubDate" if entry.date.is_empty() => {
+                    entry.date = normalize_date(text);
+                }
+                _ => {}
+            }
+        } else {
+            match name.as_str() {
+                "title" if self.title.is_none() => self.title = Some(text.trim().to_string()),
+                "subtitle" | "description" if self.subtitle.is_none() => {
+                    self.subtitle = Some(text.trim().to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn sort(&mut self) {
+        self.entries.sort_by(|a, b| b.date.cmp(&a.date));
+    }
+
+    /// Formats the feed as a gemtext page. The output is itself a valid
+    /// gemfeed.
+    #[must_use]
+    pub fn to_gemtext(&self) -> String {
+        let mut page = format!("# {}\n", self.title.as_deref().unwrap_or(&self.url));
+        if let Some(ref subtitle) = self.subtitle {
+            let _ = writeln!(page, "## {subtitle}");
+        }
+        page.push('\n');
+        for entry in &self.entries {
+            let _ = writeln!(page, "=> {} {} - {}", entry.url, entry.date, entry.title);
+        }
+        page
+    }
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).to_string()
+}
+
+/// Returns the target of an Atom `<link>` element, skipping links which do
+/// not point to an alternate representation of the entry
+fn atom_href(e: &BytesStart) -> Option<String> {
+    let mut href = None;
+    for attr in e.attributes().flatten() {
+        match attr.key.local_name().as_ref() {
+            b"href" => href = attr.unescape_value().ok().map(|v| v.to_string()),
+            b"rel" if attr.value.as_ref() != b"alternate" => return None,
+            _ => {}
+        }
+    }
+    href
+}
+
+fn resolve(base: Option<&Url>, href: &str) -> String {
+    match base.map(|b| b.join(href)) {
+        Some(Ok(url)) => url.to_string(),
+        _ => href.to_string(),
+    }
+}
+
+fn is_iso_date(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 10
+        && b[4] == b'-'
+        && b[7] == b'-'
+        && b.iter()
+            .enumerate()
+            .all(|(i, c)| i == 4 || i == 7 || c.is_ascii_digit())
+}
+
+/// Converts Atom (RFC 3339) and RSS (RFC 822) dates into `YYYY-MM-DD` form,
+/// returning the trimmed original if the format is not recognized
+fn normalize_date(date: &str) -> String {
+    let date = date.trim();
+    if let Some(d) = date.get(..10).filter(|d| is_iso_date(d)) {
+        return d.to_string();
+    }
+    // RFC 822, eg "Mon, 02 Jan 2023 15:04:05 +0000"
+    let mut parts = date
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|p| !p.is_empty())
+        .skip_while(|p| p.parse::<u32>().is_err());
+    if let (Some(day), Some(month), Some(year)) = (parts.next(), parts.next(), parts.next()) {
+        let months = [
+            "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+        ];
+        let month = month.to_lowercase();
+        if let (Ok(day), Some(month), Ok(year)) = (
+            day.parse::<u32>(),
+            months.iter().position(|m| month.starts_with(m)),
+            year.parse::<u32>(),
+        ) {
+            return format!("{year:04}-{:02}-{day:02}", month + 1);
+        }
+    }
+    date.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Example Gemlog</title>
+  <subtitle>Notes &amp; musings</subtitle>
+  <link rel="self" href="gemini://example.org/atom.xml"/>
+  <entry>
+    <title>First post</title>
+    <link rel="enclosure" href="gemini://example.org/first.mp3"/>
+    <link href="first.gmi"/>
+    <published>2022-12-30T09:00:00Z</published>
+  </entry>
+  <entry>
+    <title type="html"><![CDATA[Second <post>]]></title>
+    <link rel="alternate" href="gemini://example.org/second.gmi"/>
+    <updated>2023-01-02T10:30:00+01:00</updated>
+    <published>2023-01-01T10:30:00+01:00</published>
+  </entry>
+  <entry>
+    <title>Self link only</title>
+    <link rel="self" href="gemini://example.org/ignored.gmi"/>
+  </entry>
+</feed>"#;
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+<rss version="2.0">
+  <channel>
+    <title>Phlog</title>
+    <link>gopher://example.org/1/phlog</link>
+    <description>A gopher phlog</description>
+    <item>
+      <title>Older</title>
+      <link>gopher://example.org/0/phlog/older.txt</link>
+      <description>Not the feed subtitle</description>
+      <pubDate>Mon, 02 Jan 2023 15:04:05 +0000</pubDate>
+    </item>
+    <item>
+      <title>Newer</title>
+      <link>gopher://example.org/0/phlog/newer.txt</link>
+      <pubDate>7 Feb 2023 08:00:00 GMT</pubDate>
+    </item>
+  </channel>
+</rss>"#;
+
+    #[test]
+    fn atom() {
+        let feed = Feed::from_xml(ATOM, "gemini://example.org/atom.xml").unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Example Gemlog"));
+        assert_eq!(feed.subtitle.as_deref(), Some("Notes & musings"));
+        assert_eq!(
+            feed.entries,
+            vec![
+                FeedEntry {
+                    url: String::from("gemini://example.org/second.gmi"),
+                    title: String::from("Second <post>"),
+                    date: String::from("2023-01-02"),
+                },
+                FeedEntry {
+                    url: String::from("gemini://example.org/first.gmi"),
+                    title: String::from("First post"),
+                    date: String::from("2022-12-30"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rss() {
+        let feed = Feed::from_xml(RSS, "gopher://example.org/0/phlog.xml").unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Phlog"));
+        assert_eq!(feed.subtitle.as_deref(), Some("A gopher phlog"));
+        let entries = feed
+            .entries
+            .iter()
+            .map(|e| (e.title.as_str(), e.url.as_str(), e.date.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                ("Newer", "gopher://example.org/0/phlog/newer.txt", "2023-02-07"),
+                ("Older", "gopher://example.org/0/phlog/older.txt", "2023-01-02"),
+            ]
+        );
+    }
+
+    #[test]
+    fn xml_which_is_not_a_feed() {
+        assert!(Feed::from_xml("<html><title>Page</title></html>", "gemini://a/").is_none());
+        assert!(Feed::from_xml("<feed><entry></feed>", "gemini://a/").is_none());
+    }
+
+    #[test]
+    fn gemfeed() {
+        let page = "# My Gemlog\n## Thoughts\n\nSome text\n\
+            => 2023-01-01-old.gmi 2023-01-01 - Old post\n\
+            => gemini://example.org/about.gmi About\n\
+            => /new.gmi 2023-03-04: New post\n\
+            => untitled.gmi 2023-02-01\n";
+        let feed = Feed::from_gemtext(page, "gemini://example.org/log/").unwrap();
+        assert_eq!(feed.title.as_deref(), Some("My Gemlog"));
+        assert_eq!(feed.subtitle.as_deref(), Some("Thoughts"));
+        let entries = feed
+            .entries
+            .iter()
+            .map(|e| (e.date.as_str(), e.title.as_str(), e.url.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                ("2023-03-04", "New post", "gemini://example.org/new.gmi"),
+                ("2023-02-01", "untitled.gmi", "gemini://example.org/log/untitled.gmi"),
+                (
+                    "2023-01-01",
+                    "Old post",
+                    "gemini://example.org/log/2023-01-01-old.gmi"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn gemtext_which_is_not_a_feed() {
+        let page = "# Home\n=> about.gmi About\n=> 2023.gmi Posts from 2023\n";
+        assert!(Feed::from_gemtext(page, "gemini://example.org/").is_none());
+        let page = "# Home\n\
+            Welcome to my capsule.\n\
+            => /log/2023-01-01.gmi 2023-01-01 Latest post\n\
+            => /log/ Gemlog\n\
+            => /about.gmi About\n";
+        assert!(Feed::from_gemtext(page, "gemini://example.org/").is_none());
+    }
+
+    #[test]
+    fn gemfeed_round_trip() {
+        let feed = Feed::from_xml(ATOM, "gemini://example.org/atom.xml").unwrap();
+        let again = Feed::from_gemtext(&feed.to_gemtext(), &feed.url).unwrap();
+        assert_eq!(again, feed);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(normalize_date("2023-01-02T10:30:00Z"), "2023-01-02");
+        assert_eq!(normalize_date(" 2023-01-02 "), "2023-01-02");
+        assert_eq!(normalize_date("Mon, 02 Jan 2023 15:04:05 +0000"), "2023-01-02");
+        assert_eq!(normalize_date("Tue, 7 Feb 2023 08:00:00 GMT"), "2023-02-07");
+        assert_eq!(normalize_date("31 December 1999"), "1999-12-31");
+        assert_eq!(normalize_date("yesterday"), "yesterday");
+    }
+}
@@ -0,0 +1,214 @@
+use {
+    super::{Feed, FeedEntry},
+    crate::savefile,
+    std::{fmt::Write, fs, io, path::Path},
+};
+
+/// A subscribed feed along with every entry seen so far
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Subscription {
+    /// The url of the feed
+    pub url: String,
+    /// The feed title, once the feed has been fetched
+    pub title: Option<String>,
+    /// The entries recorded for this feed, sorted newest first
+    pub entries: Vec<FeedEntry>,
+    /// The error encountered during the most recent update, if any
+    pub error: Option<String>,
+}
+
+impl Subscription {
+    fn display_title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.url)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+    pub items: Vec<Subscription>,
+}
+
+impl Subscriptions {
+    /// Adds a subscription, returning `false` if the url was already present
+    pub fn add(&mut self, url: &str) -> bool {
+        if self.items.iter().any(|s| s.url == url) {
+            false
+        } else {
+            self.items.push(Subscription {
+                url: url.to_string(),
+                ..Subscription::default()
+            });
+            true
+        }
+    }
+
+    /// Removes a subscription, returning `false` if the url was not present
+    pub fn remove(&mut self, url: &str) -> bool {
+        let len = self.items.len();
+        self.items.retain(|s| s.url != url);
+        len != self.items.len()
+    }
+
+    /// Reads subscriptions from a file written by [`Subscriptions::save`]. A
+    /// missing file is not an error, and results in no subscriptions.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(data) => Ok(Self::from_file(&data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the subscriptions, along with every entry seen so far, to a file
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        savefile::write(path, &self.to_file())
+    }
+
+    /// Each subscription is written as a line holding its url and title,
+    /// followed by an indented line for each entry holding its url, date and
+    /// title. Fields are separated by tabs.
+    fn to_file(&self) -> String {
+        let mut data = String::new();
+        for sub in &self.items {
+            let title = sub.title.as_deref().unwrap_or_default();
+            let _ = writeln!(data, "{}\t{}", sub.url, file_field(title));
+            for e in &sub.entries {
+                let _ = writeln!(
+                    data,
+                    "\t{}\t{}\t{}",
+                    e.url,
+                    file_field(&e.date),
+                    file_field(&e.title)
+                );
+            }
+        }
+        data
+    }
+
+    fn from_file(data: &str) -> Self {
+        let mut subscriptions = Self::default();
+        for line in data.lines() {
+            if let Some(entry) = line.strip_prefix('\t') {
+                let mut fields = entry.splitn(3, '\t');
+                if let (Some(sub), Some(url), Some(date), Some(title)) = (
+                    subscriptions.items.last_mut(),
+                    fields.next(),
+                    fields.next(),
+                    fields.next(),
+                ) {
+                    sub.entries.push(FeedEntry {
+                        url: url.to_string(),
+                        title: title.to_string(),
+                        date: date.to_string(),
+                    });
+                }
+            } else if !line.is_empty() {
+                let (url, title) = line.split_once('\t').unwrap_or((line, ""));
+                subscriptions.items.push(Subscription {
+                    url: url.to_string(),
+                    title: (!title.is_empty()).then(|| title.to_string()),
+                    ..Subscription::default()
+                });
+            }
+        }
+        subscriptions
+    }
+
+    pub fn urls(&self) -> Vec<String> {
+        self.items.iter().map(|s| s.url.clone()).collect()
+    }
+
+    /// Records the result of fetching a subscribed feed, returning the number
+    /// of entries which had not been seen before
+    pub fn update(&mut self, url: &str, result: Result<Feed, String>) -> usize {
+        let Some(sub) = self.items.iter_mut().find(|s| s.url == url) else {
+            return 0;
+        };
+        match result {
+            Ok(feed) => {
+                sub.error = None;
+                if feed.title.is_some() {
+                    sub.title = feed.title;
+                }
+                let mut new = 0;
+                for entry in feed.entries {
+                    if !sub.entries.iter().any(|e| e.url == entry.url) {
+                        sub.entries.push(entry);
+                        new += 1;
+                    }
+                }
+                sub.entries.sort_by(|a, b| b.date.cmp(&a.date));
+                new
+            }
+            Err(e) => {
+                sub.error = Some(e);
+                0
+            }
+        }
+    }
+
+    /// Generates the aggregated gemtext page listing the entries of every
+    /// subscribed feed, newest first
+    pub fn to_gemtext(&self) -> String {
+        let mut entries = self
+            .items
+            .iter()
+            .flat_map(|s| s.entries.iter().map(move |e| (s.display_title(), e)))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.1.date.cmp(&a.1.date));
+        let mut page = String::from("# Feeds\n\n");
+        if entries.is_empty() {
+            page.push_str("No entries\n");
+        }
+        let mut date = "";
+        for (title, entry) in entries {
+            if entry.date != date {
+                date = &entry.date;
+                let _ = writeln!(page, "\n## {date}");
+            }
+            let _ = writeln!(
+                page,
+                "=> {} {} - {title}: {}",
+                entry.url, entry.date, entry.title
+            );
+        }
+        page.push_str("\n## Subscriptions\n");
+        for sub in &self.items {
+            let _ = writeln!(page, "=> {} {}", sub.url, sub.display_title());
+            if let Some(ref e) = sub.error {
+                let _ = writeln!(page, "* Last update failed: {e}");
+            }
+        }
+        page
+    }
+}
+
+/// Replaces the characters used to separate fields and lines in the
+/// subscriptions file
+fn file_field(text: &str) -> String {
+    text.replace(['\t', '\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_round_trip() {
+        let mut subscriptions = Subscriptions::default();
+        subscriptions.add("gemini://example.org/gemlog/");
+        subscriptions.add("gopher://example.org/1/phlog");
+        subscriptions.items[0].title = Some(String::from("Example\tGemlog"));
+        subscriptions.items[0].entries.push(FeedEntry {
+            url: String::from("gemini://example.org/gemlog/first.gmi"),
+            title: String::from("First post"),
+            date: String::from("2023-01-02"),
+        });
+        let loaded = Subscriptions::from_file(&subscriptions.to_file());
+        assert_eq!(loaded.urls(), subscriptions.urls());
+        assert_eq!(loaded.items[0].title.as_deref(), Some("Example Gemlog"));
+        assert_eq!(loaded.items[0].entries, subscriptions.items[0].entries);
+        assert_eq!(loaded.items[1].title, None);
+        assert!(loaded.items[1].entries.is_empty());
+    }
+}
@@ -0,0 +1,284 @@
+use {std::collections::HashMap, url::Url};
+
+/// The most items whose attributes are requested one at a time, when a
+/// server does not return the attributes of a whole menu at once
+pub(crate) const MAX_ITEM_REQUESTS: usize = 16;
+
+/// The location of a gopher item, as it appears in a menu line
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct Location {
+    pub host: String,
+    pub port: u16,
+    pub selector: String,
+}
+
+impl Location {
+    /// Splits a gopher url into its host, port and selector. The item type is
+    /// dropped, as is the query, which holds the terms of a search.
+    pub fn from_url(url: &str) -> Option<Self> {
+        let url = Url::parse(url).ok().filter(|u| u.scheme() == "gopher")?;
+        let host = url
+            .host_str()?
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let path = url.path();
+        let mut path = path.strip_prefix('/').unwrap_or(path).chars();
+        path.next();
+        let selector = path.as_str();
+        let selector =
+            urlencoding::decode(selector).map_or(selector.to_string(), |s| s.to_string());
+        Some(Self {
+            host: host.to_string(),
+            port: url.port_or_known_default().unwrap_or(70),
+            selector,
+        })
+    }
+
+    fn from_menu_line(line: &str) -> Option<Self> {
+        let mut fields = line.get(1..)?.split('\t').skip(1);
+        let selector = fields.next()?.to_string();
+        let host = fields.next()?.to_string();
+        let port = fields.next()?.trim().parse().ok()?;
+        Some(Self {
+            host,
+            port,
+            selector,
+        })
+    }
+}
+
+/// A link to a Gopher+ item on the current page, kept so that its attributes
+/// can be displayed once they have been retrieved
+pub(crate) struct PlusLink {
+    pub location: Location,
+    pub url: String,
+    pub display: String,
+    pub label: gtk::Label,
+    /// Marks the line following the link, where the abstract is inserted
+    pub mark: gtk::TextMark,
+}
+
+impl Drop for PlusLink {
+    fn drop(&mut self) {
+        if let Some(buf) = self.mark.buffer() {
+            buf.delete_mark(&self.mark);
+        }
+    }
+}
+
+/// Returns the locations of every item in a gopher menu which is marked as
+/// supporting Gopher+, by a '+' or '?' in the fifth field
+pub(crate) fn plus_items(map: &str) -> Vec<Location> {
+    map.lines()
+        .filter(|line| {
+            matches!(
+                line.trim_end().split('\t').nth(4),
+                Some(plus) if plus.starts_with('+') || plus.starts_with('?')
+            )
+        })
+        .filter_map(Location::from_menu_line)
+        .collect()
+}
+
+/// The attribute blocks of a Gopher+ item
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Attributes {
+    pub admin: Vec<String>,
+    pub views: Vec<String>,
+    pub abstract_: Vec<String>,
+}
+
+/// Parses the response to a `!` (single item) or `$` (whole menu) attribute
+/// request. Each item's attributes begin with a `+INFO` block holding its
+/// menu line.
+pub(crate) fn parse_attributes(data: &str) -> HashMap<Location, Attributes> {
+    let mut items = HashMap::new();
+    let mut current: Option<(Location, Attributes)> = None;
+    let mut block = String::new();
+    for line in data.lines() {
+        if let Some(rest) = line.strip_prefix('+') {
+            let (name, value) = rest.split_once(':').unwrap_or((rest, ""));
+            block = name.to_string();
+            if name == "INFO" {
+                if let Some((location, attributes)) = current.take() {
+                    items.insert(location, attributes);
+                }
+                current = Location::from_menu_line(value.trim_start())
+                    .map(|l| (l, Attributes::default()));
+            }
+        } else if let (Some((_, attributes)), Some(value)) =
+            (current.as_mut(), line.strip_prefix(' '))
+        {
+            match block.as_str() {
+                "ADMIN" => attributes.admin.push(value.to_string()),
+                "ABSTRACT" => attributes.abstract_.push(value.to_string()),
+                "VIEWS" => {
+                    // eg "application/postscript En_US: <10k>"
+                    let view = value.split_once(':').map_or(value, |(v, _)| v).trim();
+                    if !view.is_empty() {
+                        attributes.views.push(view.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    if let Some((location, attributes)) = current {
+        items.insert(location, attributes);
+    }
+    items
+}
+
+/// Strips the Gopher+ header from a response. A length of -1 means that the
+/// data is terminated by a line containing a single '.', -2 that it continues
+/// until the connection is closed. A header beginning with '-' indicates an
+/// error, with the message following.
+pub(crate) fn strip_header(data: &[u8]) -> Result<&[u8], String> {
+    let end = data
+        .windows(2)
+        .position(|w| w == b"\r\n")
+        .ok_or_else(|| String::from("Invalid Gopher+ response"))?;
+    let header = String::from_utf8_lossy(&data[..end]);
+    let body = &data[end + 2..];
+    if header.starts_with('-') {
+        return Err(String::from_utf8_lossy(body).trim().to_string());
+    }
+    match header.trim_start_matches('+').trim() {
+        "-1" => Ok(body
+            .strip_suffix(b".\r\n")
+            .or_else(|| body.strip_suffix(b".\n"))
+            .unwrap_or(body)),
+        "-2" => Ok(body),
+        len => match len.parse::<usize>() {
+            Ok(len) => Ok(&body[..len.min(body.len())]),
+            Err(_) => Err(String::from("Invalid Gopher+ response")),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(host: &str, port: u16, selector: &str) -> Location {
+        Location {
+            host: host.to_string(),
+            port,
+            selector: selector.to_string(),
+        }
+    }
+
+    #[test]
+    fn locations() {
+        assert_eq!(
+            Location::from_menu_line("0About\t/about.txt\texample.org\t7070\t+"),
+            Some(location("example.org", 7070, "/about.txt"))
+        );
+        assert_eq!(
+            Location::from_url("gopher://example.org:7070/0/about.txt"),
+            Location::from_menu_line("0About\t/about.txt\texample.org\t7070")
+        );
+        assert_eq!(
+            Location::from_url("gopher://example.org/7/find%20it?terms"),
+            Some(location("example.org", 70, "/find it"))
+        );
+        assert_eq!(
+            Location::from_url("gopher://[::1]:7070/1/menu"),
+            Some(location("::1", 7070, "/menu"))
+        );
+        assert_eq!(
+            Location::from_url("gopher://example.org"),
+            Some(location("example.org", 70, ""))
+        );
+        assert_eq!(Location::from_url("gemini://example.org/"), None);
+        assert_eq!(Location::from_menu_line("0No port\t/x\texample.org"), None);
+        assert_eq!(
+            Location::from_menu_line("0Bad port\t/x\texample.org\tx"),
+            None
+        );
+        assert_eq!(Location::from_menu_line(""), None);
+    }
+
+    #[test]
+    fn plus_menu_items() {
+        let map = "iWelcome\t\terror.host\t1\r\n\
+            0Plain\t/plain.txt\texample.org\t70\r\n\
+            0Plus\t/plus.txt\texample.org\t70\t+\r\n\
+            7Search\t/search\texample.org\t70\t?\r\n\
+            1Other\t/other\texample.org\t70\t-\r\n\
+            .\r\n";
+        assert_eq!(
+            plus_items(map),
+            vec![
+                location("example.org", 70, "/plus.txt"),
+                location("example.org", 70, "/search"),
+            ]
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        let data = "+INFO: 0Plus\t/plus.txt\texample.org\t70\t+\r\n\
+            +ADMIN:\r\n \
+            Admin: Jane <jane@example.org>\r\n \
+            Mod-Date: <20230102103000>\r\n\
+            +VIEWS:\r\n \
+            text/plain: <2k>\r\n \
+            application/postscript En_US: <10k>\r\n \
+            \r\n\
+            +ABSTRACT:\r\n \
+            An item with\r\n \
+            two lines of abstract\r\n\
+            +INFO: 1Menu\t/menu\texample.org\t70\t+\r\n\
+            +ADMIN:\r\n \
+            Admin: Joe\r\n\
+            +INFO: not a menu line\r\n\
+            +ADMIN:\r\n \
+            Admin: Nobody\r\n";
+        let items = parse_attributes(data);
+        assert_eq!(items.len(), 2);
+        let plus = &items[&location("example.org", 70, "/plus.txt")];
+        assert_eq!(
+            plus.admin,
+            vec![
+                "Admin: Jane <jane@example.org>",
+                "Mod-Date: <20230102103000>"
+            ]
+        );
+        assert_eq!(
+            plus.views,
+            vec!["text/plain", "application/postscript En_US"]
+        );
+        assert_eq!(
+            plus.abstract_,
+            vec!["An item with", "two lines of abstract"]
+        );
+        let menu = &items[&location("example.org", 70, "/menu")];
+        assert_eq!(menu.admin, vec!["Admin: Joe"]);
+        assert!(menu.views.is_empty());
+        assert!(parse_attributes("").is_empty());
+        assert!(parse_attributes(" Admin: orphaned\r\n").is_empty());
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(strip_header(b"+-1\r\nline\r\n.\r\n"), Ok(&b"line\r\n"[..]));
+        assert_eq!(strip_header(b"+-1\r\nline\n.\n"), Ok(&b"line\n"[..]));
+        assert_eq!(
+            strip_header(b"+-2\r\nuntil closed"),
+            Ok(&b"until closed"[..])
+        );
+        assert_eq!(strip_header(b"+5\r\nfive bytes"), Ok(&b"five "[..]));
+        assert_eq!(strip_header(b"+50\r\nshort"), Ok(&b"short"[..]));
+        assert_eq!(
+            strip_header(b"--1\r\n1 Item is not available\r\n.\r\n"),
+            Err(String::from("1 Item is not available\r\n."))
+        );
+        assert_eq!(
+            strip_header(b"--2\r\n3 Try again later"),
+            Err(String::from("3 Try again later"))
+        );
+        assert!(strip_header(b"+abc\r\ndata").is_err());
+        assert!(strip_header(b"no header").is_err());
+    }
+}
@@ -1,13 +1,25 @@
 use {
+    crate::{
+        animation::Animation,
+        bookmarks::Bookmarks,
+        feed::Subscriptions,
+        gopherplus::{Attributes, Location, PlusLink},
+        pagelink::{LinkHint, RenderedLink},
+        Feed, LinkIcon, NavigationTrigger, Palette, Theme, THEME_PRIORITY,
+    },
     gtk::{
-        glib,
+        gdk, glib,
         glib::{subclass::Signal, Properties},
         pango::{FontDescription, Style, Weight},
         prelude::*,
         subclass::prelude::*,
     },
     once_cell::sync::Lazy,
-    std::cell::RefCell,
+    std::{
+        cell::{Cell, RefCell},
+        collections::HashMap,
+        rc::Rc,
+    },
 };
 
 mod buffer;
@@ -20,6 +32,93 @@ pub(crate) use history::History;
 pub struct GemView {
     pub(crate) history: RefCell<History>,
     pub(crate) buffer: RefCell<Buffer>,
+    pub(crate) feed: RefCell<Option<Feed>>,
+    /// The Gopher+ items linked from the current page
+    pub(crate) plus_links: RefCell<Vec<PlusLink>>,
+    /// The Gopher+ attributes fetched for the menu at the given url, which are
+    /// kept so that the menu can be rendered again without fetching them
+    pub(crate) gopher_attributes: RefCell<Option<(String, HashMap<Location, Attributes>)>>,
+    /// The animation shown on the current page, if any, which is resized in
+    /// place when the zoom changes
+    pub(crate) animation: RefCell<Option<Rc<Animation>>>,
+    /// Set while the page shows an input form, which is not rendered from the
+    /// page source
+    pub(crate) input_form: Cell<bool>,
+    pub(crate) subscriptions: RefCell<Subscriptions>,
+    #[property(get, set)]
+    pub(crate) feeds_file: RefCell<String>,
+    /// Set when the feeds file could not be loaded, so that it is not saved
+    /// over
+    pub(crate) feeds_unreadable: Cell<bool>,
+    /// The gemini proxy used for each scheme, as listed by the "proxies"
+    /// property
+    pub(crate) proxy_map: RefCell<HashMap<String, String>>,
+    /// A comma separated list of `scheme=url` pairs, eg
+    /// "https=gemini://localhost:1965"
+    #[property(get, set)]
+    pub(crate) proxies: RefCell<String>,
+    #[property(get, set)]
+    pub(crate) socks_proxy: RefCell<String>,
+    /// The encoding the current page was decoded with
+    #[property(get)]
+    pub(crate) page_encoding: RefCell<String>,
+    /// The charset given by the server for the current page, kept so that the
+    /// page can be decoded again when "encoding-override" changes
+    pub(crate) page_charset: RefCell<Option<String>>,
+    #[property(get, set)]
+    pub(crate) encoding_override: RefCell<String>,
+    #[property(get, set)]
+    pub(crate) page_language: RefCell<String>,
+    /// A theme set by the application, overriding the light and dark presets
+    pub(crate) theme: RefCell<Option<Theme>>,
+    pub(crate) css: gtk::CssProvider,
+    #[property(get, set)]
+    pub(crate) host_colors: Cell<bool>,
+    pub(crate) host_palettes: RefCell<HashMap<String, Palette>>,
+    /// The host of the current page, which selects the palette used when the
+    /// "host-colors" property is set
+    pub(crate) current_host: RefCell<String>,
+    pub(crate) link_icons: RefCell<HashMap<String, LinkIcon>>,
+    pub(crate) links: RefCell<Vec<RenderedLink>>,
+    #[property(get, set)]
+    pub(crate) link_tooltips: Cell<bool>,
+    /// Set while the pointer is over a link
+    pub(crate) link_hovered: Cell<bool>,
+    /// How the link about to be activated was triggered, with the modifiers
+    /// held at the time
+    pub(crate) pending_navigation: Cell<Option<(NavigationTrigger, gdk::ModifierType)>>,
+    pub(crate) link_hints: RefCell<Vec<LinkHint>>,
+    /// The keys typed so far while link hints are shown
+    pub(crate) hint_input: RefCell<String>,
+    /// Set while a "links-changed" signal is waiting to be emitted
+    pub(crate) links_changed_pending: Cell<bool>,
+    #[property(get, set, minimum = 0.25, maximum = 4.0, default = 1.0)]
+    pub(crate) zoom: Cell<f64>,
+    /// The widest the text column may be, in pixels or, if
+    /// "content-width-in-chars" is set, in characters of the paragraph font.
+    /// Zero leaves the width unlimited.
+    #[property(get, set)]
+    pub(crate) max_content_width: Cell<u32>,
+    #[property(get, set)]
+    pub(crate) content_width_in_chars: Cell<bool>,
+    /// The least space in pixels between the text and the sides of the view
+    #[property(get, set, minimum = 0)]
+    pub(crate) page_margin: Cell<i32>,
+    /// The width in pixels of the text column, which blocks that wrap their
+    /// text are sized to fit
+    pub(crate) content_width: Cell<i32>,
+    /// The width the view was last allocated, so that the text column is only
+    /// laid out again when it changes
+    pub(crate) allocated_width: Cell<i32>,
+    pub(crate) feed_timer: RefCell<Option<glib::SourceId>>,
+    #[property(get, set)]
+    pub(crate) feed_update_interval: Cell<u32>,
+    pub(crate) bookmarks: RefCell<Bookmarks>,
+    #[property(get, set)]
+    pub(crate) bookmarks_file: RefCell<String>,
+    /// Set when the bookmarks file could not be loaded, so that it is not
+    /// saved over
+    pub(crate) bookmarks_unreadable: Cell<bool>,
     #[property(get, set)]
     pub(crate) font_paragraph: RefCell<String>,
     #[property(get, set)]
@@ -33,6 +132,16 @@ pub struct GemView {
     #[property(get, set)]
     pub(crate) font_h3: RefCell<String>,
     #[property(get, set)]
+    pub(crate) autoplay_animations: Cell<bool>,
+    /// Plays audio and video content in the view rather than emitting the
+    /// "request-download" signal for it. On by default.
+    #[property(get, set)]
+    pub(crate) play_media: Cell<bool>,
+    #[property(get, set)]
+    pub(crate) inline_input: Cell<bool>,
+    #[property(get, set)]
+    pub(crate) multiline_input: Cell<bool>,
+    #[property(get, set)]
     pub(crate) paragraph_tag: RefCell<gtk::TextTag>,
     #[property(get, set)]
     pub(crate) h1_tag: RefCell<gtk::TextTag>,
@@ -69,6 +178,31 @@ impl ObjectImpl for GemView {
         let obj = self.obj();
         obj.set_editable(false);
         obj.set_cursor_visible(false);
+        obj.set_autoplay_animations(true);
+        obj.set_play_media(true);
+        obj.set_link_tooltips(true);
+        obj.connect_notify_local(Some("link-tooltips"), |obj, _| {
+            obj.apply_link_tooltips();
+        });
+        obj.connect_notify_local(Some("feed-update-interval"), |obj, _| {
+            obj.restart_feed_timer();
+        });
+        obj.set_feed_update_interval(3600);
+        obj.connect_notify_local(Some("feeds-file"), |obj, _| {
+            obj.load_subscriptions();
+        });
+        obj.connect_notify_local(Some("bookmarks-file"), |obj, _| {
+            obj.load_bookmarks();
+        });
+        obj.connect_notify_local(Some("page-language"), |obj, _| {
+            obj.apply_page_language();
+        });
+        obj.connect_notify_local(Some("proxies"), |obj, _| {
+            obj.parse_proxies();
+        });
+        obj.connect_notify_local(Some("encoding-override"), |obj, _| {
+            obj.re_render();
+        });
         *self.history.borrow_mut() = History::default();
         let buffer = obj.buffer();
         let mut font = FontDescription::new();
@@ -132,6 +266,46 @@ impl ObjectImpl for GemView {
             .unwrap();
         obj.set_h1_tag(h1tag);
         obj.bind_properties();
+        #[allow(deprecated)]
+        obj.style_context().add_provider(&self.css, THEME_PRIORITY);
+        obj.apply_theme();
+        obj.connect_notify_local(Some("host-colors"), |obj, _| {
+            obj.apply_theme();
+        });
+        for font in ["font-paragraph", "font-pre", "font-quote"] {
+            obj.connect_notify_local(Some(font), |obj, _| {
+                obj.re_render();
+            });
+        }
+        obj.set_zoom(1.0);
+        obj.connect_notify_local(Some("zoom"), |obj, _| {
+            obj.apply_zoom();
+        });
+        obj.set_top_margin(25);
+        obj.set_bottom_margin(25);
+        obj.set_page_margin(45);
+        for prop in [
+            "max-content-width",
+            "content-width-in-chars",
+            "page-margin",
+            "font-paragraph",
+            "zoom",
+        ] {
+            obj.connect_notify_local(Some(prop), |obj, _| {
+                obj.update_text_column();
+            });
+        }
+        if let Some(settings) = gtk::Settings::default() {
+            let viewer = obj.downgrade();
+            settings.connect_notify_local(
+                Some("gtk-application-prefer-dark-theme"),
+                move |_, _| {
+                    if let Some(viewer) = viewer.upgrade() {
+                        viewer.apply_theme();
+                    }
+                },
+            );
+        }
     }
 
     fn signals() -> &'static [Signal] {
@@ -165,11 +339,39 @@ impl ObjectImpl for GemView {
                     .param_types([glib::Type::STRING, glib::Type::STRING])
                     .build(),
                 Signal::builder("request-input-sensitive")
-                    .param_types([glib::Type::STRING])
+                    .param_types([glib::Type::STRING, glib::Type::STRING])
                     .build(),
                 Signal::builder("request-upload")
                     .param_types([glib::Type::STRING])
                     .build(),
+                Signal::builder("feeds-updated")
+                    .param_types([glib::Type::U32])
+                    .build(),
+                Signal::builder("feeds-file-error")
+                    .param_types([glib::Type::STRING])
+                    .build(),
+                Signal::builder("bookmarks-changed").build(),
+                Signal::builder("bookmarks-file-error")
+                    .param_types([glib::Type::STRING])
+                    .build(),
+                Signal::builder("links-changed").build(),
+                Signal::builder("link-hovered")
+                    .param_types([glib::Type::STRING])
+                    .build(),
+                Signal::builder("link-unhovered").build(),
+                Signal::builder("decide-navigation")
+                    .param_types([
+                        glib::Type::STRING,
+                        NavigationTrigger::static_type(),
+                        gdk::ModifierType::static_type(),
+                    ])
+                    .return_type::<String>()
+                    // The first handler to decide stops the emission
+                    .accumulator(|_, acc, value| {
+                        *acc = value.clone();
+                        !matches!(value.get::<Option<String>>(), Ok(Some(_)))
+                    })
+                    .build(),
             ]
         });
         SIGNALS.as_ref()
@@ -177,6 +379,20 @@ impl ObjectImpl for GemView {
 }
 
 // Trait shared by all widgets
-impl WidgetImpl for GemView {}
+impl WidgetImpl for GemView {
+    fn size_allocate(&self, width: i32, height: i32, baseline: i32) {
+        self.parent_size_allocate(width, height, baseline);
+        if self.allocated_width.replace(width) != width {
+            // Changing the margins queues another resize, which is not allowed
+            // while the view is being allocated
+            let obj = self.obj().downgrade();
+            glib::idle_add_local_once(move || {
+                if let Some(obj) = obj.upgrade() {
+                    obj.update_text_column();
+                }
+            });
+        }
+    }
+}
 
 impl TextViewImpl for GemView {}
@@ -0,0 +1,143 @@
+use url::Url;
+
+/// The item type of a gopher menu entry, as defined by RFC 1436 along with
+/// the commonly used extensions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ItemType {
+    Text,
+    Directory,
+    Cso,
+    Error,
+    BinHex,
+    Dos,
+    Uuencoded,
+    Search,
+    Telnet,
+    Binary,
+    Mirror,
+    Gif,
+    Image,
+    Tn3270,
+    Document,
+    Html,
+    Info,
+    Sound,
+    Calendar,
+    Mime,
+    Video,
+    Pdf,
+    Xml,
+    Unknown(char),
+}
+
+impl From<char> for ItemType {
+    fn from(c: char) -> Self {
+        match c {
+            '0' => Self::Text,
+            '1' => Self::Directory,
+            '2' => Self::Cso,
+            '3' => Self::Error,
+            '4' => Self::BinHex,
+            '5' => Self::Dos,
+            '6' => Self::Uuencoded,
+            '7' => Self::Search,
+            '8' => Self::Telnet,
+            '9' => Self::Binary,
+            '+' => Self::Mirror,
+            'g' => Self::Gif,
+            'I' | 'p' => Self::Image,
+            'T' => Self::Tn3270,
+            'd' => Self::Document,
+            'h' => Self::Html,
+            'i' => Self::Info,
+            's' => Self::Sound,
+            'c' => Self::Calendar,
+            'M' => Self::Mime,
+            ';' => Self::Video,
+            'P' => Self::Pdf,
+            'X' => Self::Xml,
+            c => Self::Unknown(c),
+        }
+    }
+}
+
+impl ItemType {
+    /// Gets the item type from the first path segment of a gopher url. A url
+    /// with an empty path refers to the server's root menu.
+    pub fn from_url(url: &str) -> Self {
+        Url::parse(url)
+            .ok()
+            .and_then(|u| type_char(&u))
+            .map_or(Self::Unknown(' '), Self::from)
+    }
+
+    /// The icon displayed in front of links of this type
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Text => "📄",
+            Self::Directory => "📁",
+            Self::Cso => "📇",
+            Self::Error => "⚠️",
+            Self::BinHex | Self::Dos | Self::Uuencoded | Self::Binary => "💾",
+            Self::Search => "🔍",
+            Self::Telnet | Self::Tn3270 => "🖥️",
+            Self::Mirror => "🔁",
+            Self::Gif | Self::Image => "🖼️",
+            Self::Document | Self::Pdf => "📑",
+            Self::Html => "🌐",
+            Self::Info => "",
+            Self::Sound => "🔊",
+            Self::Calendar => "📅",
+            Self::Mime => "✉️",
+            Self::Video => "🎞️",
+            Self::Xml => "📰",
+            Self::Unknown(_) => "🕳️",
+        }
+    }
+
+    /// Returns `true` for items which should always be downloaded rather
+    /// than displayed
+    pub fn is_binary(self) -> bool {
+        matches!(
+            self,
+            Self::BinHex | Self::Dos | Self::Uuencoded | Self::Binary | Self::Document | Self::Pdf
+        )
+    }
+
+    /// Returns `true` for items which are not links, but lines of text
+    pub fn is_text_line(self) -> bool {
+        matches!(self, Self::Info | Self::Error)
+    }
+}
+
+/// Gets the character giving the item type of a gopher url, which starts its
+/// path, or `None` if the url is not a gopher url. A url with an empty path
+/// refers to the server's root menu, of type '1'.
+pub(crate) fn type_char(url: &Url) -> Option<char> {
+    (url.scheme() == "gopher").then(|| {
+        url.path()
+            .trim_start_matches('/')
+            .chars()
+            .next()
+            .unwrap_or('1')
+    })
+}
+
+/// Converts the url of a gopher telnet item into a `telnet://` url. The
+/// selector, if any, is the suggested login name.
+pub(crate) fn telnet_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    let selector = url.path().trim_start_matches('/').get(1..).unwrap_or("");
+    let login = urlencoding::decode(selector).map_or(String::new(), |l| l.to_string());
+    let mut telnet = String::from("telnet://");
+    if !login.is_empty() {
+        telnet.push_str(&urlencoding::encode(&login));
+        telnet.push('@');
+    }
+    telnet.push_str(host);
+    if let Some(port) = url.port() {
+        telnet.push_str(&format!(":{port}"));
+    }
+    Some(telnet)
+}
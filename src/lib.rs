@@ -5,34 +5,74 @@
 use {
     glib::{Continue, MainContext, Object, PRIORITY_DEFAULT},
     gtk::{
-        gdk_pixbuf::Pixbuf,
-        gio::{Cancellable, MemoryInputStream, Menu, MenuItem, SimpleAction, SimpleActionGroup},
+        gdk::ModifierType,
+        gdk_pixbuf::{Pixbuf, PixbufAnimation},
+        gio::{
+            self, Cancellable, MemoryInputStream, Menu, MenuItem, SimpleAction, SimpleActionGroup,
+        },
         glib,
-        pango::FontDescription,
+        pango::{self, FontDescription},
         prelude::*,
         subclass::prelude::*,
     },
-    std::{borrow::Cow, path::PathBuf, thread},
-    textwrap::fill,
+    std::{
+        borrow::Cow,
+        cell::Cell,
+        collections::HashMap,
+        path::PathBuf,
+        rc::Rc,
+        thread,
+        time::{Duration, SystemTime},
+    },
     url::Url,
 };
 
+mod animation;
+mod bidi;
+mod bookmarks;
+mod charset;
+mod feed;
+mod gopherplus;
 mod imp;
+mod itemtype;
+mod linkicon;
+mod navigation;
+mod pagelink;
+mod request;
+mod savefile;
+mod theme;
 mod traits;
 mod upload;
+use animation::Animation;
+pub use bookmarks::Bookmark;
+pub use feed::{Feed, FeedEntry, Subscription};
+use gopherplus::{Attributes, Location, PlusLink};
+use itemtype::ItemType;
+pub use linkicon::LinkIcon;
+pub use navigation::{NavigationDecision, NavigationTrigger};
+use pagelink::{LinkHint, RenderedLink};
+pub use pagelink::{LinkKind, PageLink};
+pub use theme::{Palette, Theme};
 use traits::ToLabel;
 
 use {
     bucky::data::{Data, DataUrl, MimeType},
     bucky::file,
-    bucky::finger,
     bucky::gemini::{self, parser::GemtextNode},
     bucky::gopher::{self, GopherMap},
-    bucky::prelude::{Content, Input, Response},
-    bucky::spartan,
+    bucky::prelude::{Content, Response},
     upload::UploadWidget,
 };
 
+/// The factor by which [`GemView::zoom_in`] and [`GemView::zoom_out`] change
+/// the "zoom" property
+const ZOOM_STEP: f64 = 1.1;
+
+/// The priority of the theme stylesheet, which is below that of application
+/// stylesheets so that the host application can restyle any part of the page
+/// through its own CSS
+const THEME_PRIORITY: u32 = gtk::STYLE_PROVIDER_PRIORITY_SETTINGS;
+
 #[derive(Clone, Copy)]
 enum TextSize {
     Paragraph,
@@ -59,10 +99,6 @@ impl GemView {
     #[allow(clippy::must_use_candidate)]
     pub fn new() -> Self {
         Object::builder()
-            .property("margin-start", &45.to_value())
-            .property("margin-end", &45.to_value())
-            .property("margin-top", &25.to_value())
-            .property("margin-bottom", &25.to_value())
             .property("wrap-mode", gtk::WrapMode::Word)
             .build()
     }
@@ -71,10 +107,6 @@ impl GemView {
     pub fn with_label(label: &str) -> Self {
         Object::builder()
             .property("label", label)
-            .property("margin-start", &45.to_value())
-            .property("margin-end", &45.to_value())
-            .property("margin-top", &25.to_value())
-            .property("margin-bottom", &25.to_value())
             .property("wrap-mode", gtk::WrapMode::Word)
             .build()
     }
@@ -94,17 +126,22 @@ impl GemView {
         let request_new_tab = SimpleAction::new("request-new-tab", Some(glib::VariantTy::STRING));
         let request_new_window =
             SimpleAction::new("request-new-window", Some(glib::VariantTy::STRING));
+        let add_bookmark =
+            SimpleAction::new("add-bookmark", Some(glib::VariantTy::new("(ss)").unwrap()));
         let group = SimpleActionGroup::new();
         group.add_action(&request_new_tab);
         group.add_action(&request_new_window);
+        group.add_action(&add_bookmark);
         let viewer = self.clone();
         request_new_tab.connect_activate(move |_, url| {
             if let Some(url) = url {
                 if let Some(url) = url.get::<String>() {
                     if let Ok(url) = urlencoding::decode(&url) {
-                        if let Ok(url) = viewer.absolute_url(&url) {
-                            viewer.emit_by_name::<()>("request-new-tab", &[&url.to_string()]);
-                        }
+                        viewer.open_elsewhere(
+                            &url,
+                            NavigationTrigger::NewTab,
+                            ModifierType::empty(),
+                        );
                     }
                 }
             }
@@ -114,14 +151,70 @@ impl GemView {
             if let Some(url) = url {
                 if let Some(url) = url.get::<String>() {
                     if let Ok(url) = urlencoding::decode(&url) {
-                        if let Ok(url) = viewer.absolute_url(&url) {
-                            viewer.emit_by_name::<()>("request-new-window", &[&url.to_string()]);
-                        }
+                        viewer.open_elsewhere(
+                            &url,
+                            NavigationTrigger::NewWindow,
+                            ModifierType::empty(),
+                        );
+                    }
+                }
+            }
+        });
+        let viewer = self.clone();
+        add_bookmark.connect_activate(move |_, param| {
+            if let Some((url, title)) = param.and_then(|p| p.get::<(String, String)>()) {
+                if let (Ok(url), Ok(title)) =
+                    (urlencoding::decode(&url), urlencoding::decode(&title))
+                {
+                    if let Ok(url) = viewer.join_url(&url) {
+                        viewer.add_bookmark(Bookmark {
+                            url: url.to_string(),
+                            title: title.to_string(),
+                            ..Bookmark::default()
+                        });
+                    }
+                }
+            }
+        });
+        let gopher_view =
+            SimpleAction::new("gopher-view", Some(glib::VariantTy::new("(ss)").unwrap()));
+        group.add_action(&gopher_view);
+        let viewer = self.clone();
+        gopher_view.connect_activate(move |_, param| {
+            if let Some((url, view)) = param.and_then(|p| p.get::<(String, String)>()) {
+                if let (Ok(url), Ok(view)) = (urlencoding::decode(&url), urlencoding::decode(&view))
+                {
+                    if let Some(url) = viewer.decide_navigation(
+                        &url,
+                        NavigationTrigger::ContextMenu,
+                        ModifierType::empty(),
+                    ) {
+                        viewer.visit_gopher_view(&url, &view);
                     }
                 }
             }
         });
+        for (name, activate) in [
+            ("zoom-in", Self::zoom_in as fn(&Self)),
+            ("zoom-out", Self::zoom_out),
+            ("zoom-reset", Self::zoom_reset),
+            ("next-link", Self::focus_next_link),
+            ("previous-link", Self::focus_previous_link),
+            ("link-hints", Self::show_link_hints),
+        ] {
+            let action = SimpleAction::new(name, None);
+            group.add_action(&action);
+            let viewer = self.clone();
+            action.connect_activate(move |_, _| activate(&viewer));
+        }
         self.insert_action_group("viewer", Some(&group));
+        let keys = gtk::EventControllerKey::new();
+        // Keys are captured before they reach a focused link, so that typing
+        // a hint does not activate it
+        keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let viewer = self.clone();
+        keys.connect_key_pressed(move |_, key, _, _| gtk::Inhibit(viewer.link_hint_key(key)));
+        self.add_controller(keys);
     }
 
     #[must_use]
@@ -176,6 +269,7 @@ impl GemView {
     /// Manually appends an item into the browser's history. Normally this function
     /// will not need to be called directly.
     pub fn append_history(&self, uri: &str) {
+        self.update_host(uri);
         let current = self.uri();
         if current != uri {
             self.imp().history.borrow_mut().append(uri.to_string());
@@ -202,9 +296,255 @@ impl GemView {
     }
 
     /// Set the contents of the buffer. Normally this function will not need to
-    /// be called directly
+    /// be called directly. The "page-language" property and any Gopher+
+    /// attributes are cleared, as they described the previous contents.
     pub fn set_buffer_content(&self, content: &[u8]) {
         self.imp().buffer.borrow_mut().content = content.to_vec();
+        *self.imp().gopher_attributes.borrow_mut() = None;
+        self.set_page_language("");
+    }
+
+    #[must_use]
+    /// Returns the feed found on the current page, if any. Gemtext pages
+    /// following the gemfeed conventions as well as Atom and RSS documents are
+    /// recognized.
+    pub fn page_feed(&self) -> Option<Feed> {
+        self.imp().feed.borrow().clone()
+    }
+
+    /// Adds a bookmark, replacing any existing bookmark for the same url. If
+    /// the "bookmarks-file" property is set the bookmarks are saved to disk.
+    pub fn add_bookmark(&self, bookmark: Bookmark) {
+        self.imp().bookmarks.borrow_mut().add(bookmark);
+        self.bookmarks_changed();
+    }
+
+    /// Removes the bookmark for `url`, returning `false` if there was none
+    pub fn remove_bookmark(&self, url: &str) -> bool {
+        let removed = self.imp().bookmarks.borrow_mut().remove(url);
+        if removed {
+            self.bookmarks_changed();
+        }
+        removed
+    }
+
+    #[must_use]
+    /// Returns all bookmarks
+    pub fn bookmarks(&self) -> Vec<Bookmark> {
+        self.imp().bookmarks.borrow().items.clone()
+    }
+
+    #[must_use]
+    /// Returns `true` if `url` has been bookmarked
+    pub fn is_bookmarked(&self, url: &str) -> bool {
+        self.imp().bookmarks.borrow().contains(url)
+    }
+
+    /// Imports bookmarks from a gemtext page, with level two headings used as
+    /// folder names. Returns the number of bookmarks added.
+    pub fn import_bookmarks_gemtext(&self, data: &str) -> usize {
+        let added = self
+            .imp()
+            .bookmarks
+            .borrow_mut()
+            .merge(bookmarks::from_gemtext(data));
+        self.bookmarks_changed();
+        added
+    }
+
+    /// Imports bookmarks from a Lagrange `bookmarks.ini` file. Returns the
+    /// number of bookmarks added.
+    pub fn import_bookmarks_lagrange(&self, data: &str) -> usize {
+        let added = self
+            .imp()
+            .bookmarks
+            .borrow_mut()
+            .merge(bookmarks::from_lagrange(data));
+        self.bookmarks_changed();
+        added
+    }
+
+    #[must_use]
+    /// Exports all bookmarks as a gemtext page
+    pub fn export_bookmarks_gemtext(&self) -> String {
+        self.imp().bookmarks.borrow().to_gemtext()
+    }
+
+    #[must_use]
+    /// Exports all bookmarks in the format of a Lagrange `bookmarks.ini` file
+    pub fn export_bookmarks_lagrange(&self) -> String {
+        self.imp().bookmarks.borrow().to_lagrange()
+    }
+
+    fn load_bookmarks(&self) {
+        let path = self.bookmarks_file();
+        self.imp().bookmarks_unreadable.set(false);
+        if path.is_empty() {
+            return;
+        }
+        match bookmarks::Bookmarks::load(&PathBuf::from(path)) {
+            Ok(b) => *self.imp().bookmarks.borrow_mut() = b,
+            Err(e) => {
+                self.imp().bookmarks_unreadable.set(true);
+                self.emit_by_name::<()>("bookmarks-file-error", &[&e.to_string()]);
+            }
+        }
+        self.emit_by_name::<()>("bookmarks-changed", &[]);
+    }
+
+    /// Saves the bookmarks and refreshes `about:bookmarks`. Bookmarks are not
+    /// saved over a file which could not be loaded, as that would replace
+    /// whatever it holds with only the bookmarks added since.
+    fn bookmarks_changed(&self) {
+        let path = self.bookmarks_file();
+        if self.imp().bookmarks_unreadable.get() {
+            let error = format!("Not saving bookmarks, as {path} could not be loaded");
+            self.emit_by_name::<()>("bookmarks-file-error", &[&error]);
+        } else if !path.is_empty() {
+            let result = self.imp().bookmarks.borrow().save(&PathBuf::from(path));
+            if let Err(e) = result {
+                self.emit_by_name::<()>("bookmarks-file-error", &[&e.to_string()]);
+            }
+        }
+        if self.uri() == "about:bookmarks" {
+            self.reload();
+        }
+        self.emit_by_name::<()>("bookmarks-changed", &[]);
+    }
+
+    /// Subscribes to the gemfeed, Atom or RSS feed at `url` and fetches it.
+    /// Feeds may be served over gemini, spartan or gopher. Returns `false` if
+    /// the url could not be parsed or is already subscribed. If the
+    /// "feeds-file" property is set the subscriptions are saved to disk.
+    pub fn add_subscription(&self, url: &str) -> bool {
+        let Ok(url) = self.join_url(url) else {
+            return false;
+        };
+        if !matches!(url.scheme(), "gemini" | "spartan" | "gopher") {
+            return false;
+        }
+        let added = self.imp().subscriptions.borrow_mut().add(url.as_str());
+        if added {
+            self.save_subscriptions();
+            self.fetch_feeds(vec![url.to_string()]);
+        }
+        added
+    }
+
+    /// Unsubscribes from the feed at `url`, returning `false` if there was no
+    /// such subscription
+    pub fn remove_subscription(&self, url: &str) -> bool {
+        let removed = self.imp().subscriptions.borrow_mut().remove(url);
+        if removed {
+            self.save_subscriptions();
+        }
+        removed
+    }
+
+    #[must_use]
+    /// Returns every feed subscription along with the entries recorded so far
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.imp().subscriptions.borrow().items.clone()
+    }
+
+    /// Fetches every subscribed feed, recording any new entries. The
+    /// "feeds-updated" signal is emitted once all feeds have been checked.
+    /// This is called periodically according to the "feed-update-interval"
+    /// property.
+    pub fn refresh_feeds(&self) {
+        let urls = self.imp().subscriptions.borrow().urls();
+        self.fetch_feeds(urls);
+    }
+
+    /// Fetches the given feeds, each on its own background thread, and emits
+    /// the "feeds-updated" signal once every one has been checked
+    fn fetch_feeds(&self, urls: Vec<String>) {
+        if urls.is_empty() {
+            return;
+        }
+        let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
+        let socks = self.socks_address();
+        let mut remaining = urls.len();
+        for url in urls {
+            let sender = sender.clone();
+            let socks = socks.clone();
+            thread::spawn(move || {
+                let result = feed::fetch(&url, socks.as_deref());
+                sender.send((url, result)).expect("Cannot send data");
+            });
+        }
+        let viewer = self.clone();
+        let mut new = 0;
+        receiver.attach(None, move |(url, result)| {
+            new += viewer.imp().subscriptions.borrow_mut().update(&url, result);
+            remaining -= 1;
+            if remaining > 0 {
+                return Continue(true);
+            }
+            viewer.save_subscriptions();
+            if viewer.uri() == "about:feeds" {
+                viewer.reload();
+            }
+            let new = u32::try_from(new).unwrap_or(u32::MAX);
+            viewer.emit_by_name::<()>("feeds-updated", &[&new]);
+            Continue(false)
+        });
+    }
+
+    fn load_subscriptions(&self) {
+        let path = self.feeds_file();
+        self.imp().feeds_unreadable.set(false);
+        if path.is_empty() {
+            return;
+        }
+        match feed::Subscriptions::load(&PathBuf::from(path)) {
+            Ok(s) => *self.imp().subscriptions.borrow_mut() = s,
+            Err(e) => {
+                self.imp().feeds_unreadable.set(true);
+                self.emit_by_name::<()>("feeds-file-error", &[&e.to_string()]);
+            }
+        }
+        if self.uri() == "about:feeds" {
+            self.reload();
+        }
+    }
+
+    /// Saves the subscriptions to the file named by the "feeds-file" property,
+    /// if it is set. As with bookmarks, a file which could not be loaded is
+    /// never saved over.
+    fn save_subscriptions(&self) {
+        let path = self.feeds_file();
+        if path.is_empty() {
+            return;
+        }
+        if self.imp().feeds_unreadable.get() {
+            let error = format!("Not saving subscriptions, as {path} could not be loaded");
+            self.emit_by_name::<()>("feeds-file-error", &[&error]);
+            return;
+        }
+        let result = self.imp().subscriptions.borrow().save(&PathBuf::from(path));
+        if let Err(e) = result {
+            self.emit_by_name::<()>("feeds-file-error", &[&e.to_string()]);
+        }
+    }
+
+    fn restart_feed_timer(&self) {
+        if let Some(id) = self.imp().feed_timer.borrow_mut().take() {
+            id.remove();
+        }
+        let interval = self.feed_update_interval();
+        if interval > 0 {
+            let viewer = self.downgrade();
+            let id = glib::timeout_add_seconds_local(interval, move || {
+                if let Some(viewer) = viewer.upgrade() {
+                    viewer.refresh_feeds();
+                    Continue(true)
+                } else {
+                    Continue(false)
+                }
+            });
+            *self.imp().feed_timer.borrow_mut() = Some(id);
+        }
     }
 
     fn get_iter(&self) -> (gtk::TextBuffer, gtk::TextIter) {
@@ -228,7 +568,6 @@ impl GemView {
             .css_classes(vec!["preformatted".to_string()])
             .build();
         let anchor = buf.create_child_anchor(&mut iter);
-        self.add_child_at_anchor(&prebox, &anchor);
         let text = glib::markup_escape_text(data);
         let font = self.font_pre();
         let label = gtk::Label::builder()
@@ -237,20 +576,42 @@ impl GemView {
             .label(format!("<span font=\"{font}\">{text}</span>"))
             .build();
         prebox.append(&label);
+        self.insert_widget(&prebox, &anchor);
     }
 
-    /// Renders a Vec<u8> into an image
+    /// Renders a Vec<u8> into an image. Animated images, such as gif or webp,
+    /// are played inline.
     pub fn render_image_from_bytes(&self, bytes: &Vec<u8>) {
         let bytes = gtk::glib::Bytes::from(bytes);
         let stream = MemoryInputStream::from_bytes(&bytes);
-        let width = self.size(gtk::Orientation::Horizontal);
-        if let Ok(pixbuf) =
-            Pixbuf::from_stream_at_scale(&stream, width, -1, true, Option::<&Cancellable>::None)
+        if let Ok(animation) =
+            PixbufAnimation::from_stream(&stream, Option::<&Cancellable>::None)
         {
+            if !animation.is_static_image() {
+                self.render_animation(&animation);
+                return;
+            }
+        }
+        let stream = MemoryInputStream::from_bytes(&bytes);
+        if let Ok(pixbuf) = Pixbuf::from_stream_at_scale(
+            &stream,
+            self.image_width(),
+            -1,
+            true,
+            Option::<&Cancellable>::None,
+        ) {
             let _img = self.render_pixbuf(&pixbuf);
         }
     }
 
+    /// Gets the width which images are scaled to, which is the width of the
+    /// view scaled by the "zoom" property
+    fn image_width(&self) -> i32 {
+        #[allow(clippy::cast_possible_truncation)]
+        let width = (f64::from(self.size(gtk::Orientation::Horizontal)) * self.zoom()) as i32;
+        width
+    }
+
     /// Renders a [`gtk::gdk_pixbuf::Pixbuf`]
     fn render_pixbuf(&self, pixbuf: &gtk::gdk_pixbuf::Pixbuf) -> gtk::Image {
         self.clear();
@@ -263,9 +624,156 @@ impl GemView {
         image
     }
 
+    /// Renders a [`gtk::gdk_pixbuf::PixbufAnimation`] along with a button to
+    /// pause and resume playback. Frames are scaled to the width of the view,
+    /// as static images are.
+    fn render_animation(&self, animation: &PixbufAnimation) {
+        self.clear();
+        let (buf, mut iter) = self.get_iter();
+        let start = SystemTime::now();
+        let picture = gtk::Picture::new();
+        picture.set_can_shrink(true);
+        picture.set_hexpand(true);
+        picture.set_halign(gtk::Align::Fill);
+        let animation = Rc::new(Animation::new(
+            &picture,
+            animation.iter(Some(start)),
+            start,
+            self.autoplay_animations(),
+            self.image_width(),
+        ));
+        let playing = animation.playing.get();
+        let toggle = gtk::ToggleButton::builder()
+            .icon_name(Self::playback_icon(playing))
+            .tooltip_text("Play/Pause")
+            .active(playing)
+            .halign(gtk::Align::Center)
+            .build();
+        let state = animation.clone();
+        toggle.connect_toggled(move |button| {
+            state.playing.set(button.is_active());
+            button.set_icon_name(Self::playback_icon(button.is_active()));
+            Self::schedule_frame(state.clone());
+        });
+        let imgbox = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .hexpand(true)
+            .halign(gtk::Align::Fill)
+            .spacing(4)
+            .css_classes(vec!["image".to_string()])
+            .build();
+        imgbox.append(&picture);
+        imgbox.append(&toggle);
+        let anchor = buf.create_child_anchor(&mut iter);
+        self.insert_widget(&imgbox, &anchor);
+        *self.imp().animation.borrow_mut() = Some(animation.clone());
+        Self::schedule_frame(animation);
+    }
+
+    fn playback_icon(playing: bool) -> &'static str {
+        if playing {
+            "media-playback-pause-symbolic"
+        } else {
+            "media-playback-start-symbolic"
+        }
+    }
+
+    /// Advances an animation by one frame after the current frame's delay.
+    /// Stops once the animation is paused, the picture has been removed from
+    /// the page or the animation has no further frames. Does nothing if a
+    /// frame is already scheduled.
+    fn schedule_frame(animation: Rc<Animation>) {
+        if !animation.playing.get() || animation.scheduled.get() {
+            return;
+        }
+        let Some(delay) = animation.delay() else {
+            return;
+        };
+        let delay = std::cmp::max(delay, Duration::from_millis(20));
+        animation.scheduled.set(true);
+        glib::timeout_add_local_once(delay, move || {
+            animation.scheduled.set(false);
+            if animation.advance(delay) {
+                Self::schedule_frame(animation);
+            }
+        });
+    }
+
+    /// Renders audio or video content along with playback controls, which
+    /// include a seek bar and the elapsed and total duration. The content is
+    /// written to a temporary file, as the GStreamer backend can only play
+    /// files and uris. The file is removed along with the player.
+    pub fn render_media_from_bytes(&self, bytes: &[u8], mime: &str) {
+        let file = match Self::write_temp_file(bytes) {
+            Ok(f) => f,
+            Err(e) => {
+                let estr = format!("{e:?}");
+                self.emit_by_name::<()>("page-load-failed", &[&estr]);
+                return;
+            }
+        };
+        self.clear();
+        let (buf, mut iter) = self.get_iter();
+        let media = gtk::MediaFile::for_file(&file);
+        let mediabox = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .hexpand(true)
+            .halign(gtk::Align::Fill)
+            .margin_bottom(8)
+            .margin_top(8)
+            .margin_start(8)
+            .margin_end(8)
+            .css_classes(vec!["media".to_string()])
+            .build();
+        if mime.starts_with("video") {
+            let video = gtk::Video::builder()
+                .media_stream(&media)
+                .hexpand(true)
+                .vexpand(true)
+                .height_request(self.height() / 2)
+                .build();
+            mediabox.append(&video);
+        } else {
+            let controls = gtk::MediaControls::builder()
+                .media_stream(&media)
+                .hexpand(true)
+                .build();
+            mediabox.append(&controls);
+        }
+        mediabox.connect_destroy(move |_| {
+            let _ = file.delete(Option::<&Cancellable>::None);
+        });
+        let anchor = buf.create_child_anchor(&mut iter);
+        self.insert_widget(&mediabox, &anchor);
+    }
+
+    /// Writes `bytes` to a new temporary file
+    fn write_temp_file(bytes: &[u8]) -> Result<gio::File, glib::Error> {
+        let (file, stream) = gio::File::new_tmp(Some("gemview-XXXXXX"))?;
+        stream
+            .output_stream()
+            .write_all(bytes, Option::<&Cancellable>::None)?;
+        stream.close(Option::<&Cancellable>::None)?;
+        Ok(file)
+    }
+
+    /// Returns `true` if the given mime type should be played in the widget
+    /// rather than requesting a download
+    fn is_playable(&self, mime: &str) -> bool {
+        self.play_media() && (mime.starts_with("audio/") || mime.starts_with("video/"))
+    }
+
+    /// Renders a [`Feed`] as a gemtext document, with entries sorted newest
+    /// first
+    pub fn render_feed(&self, feed: Feed) {
+        self.render_gmi(&feed.to_gemtext());
+        *self.imp().feed.borrow_mut() = Some(feed);
+    }
+
     /// Renders the given `&str` as a gemtext document
     pub fn render_gmi(&self, data: &str) {
         self.clear();
+        *self.imp().feed.borrow_mut() = Feed::from_gemtext(data, &self.uri());
         let nodes = gemini::parser::Parser::default().parse(data);
         for node in nodes {
             match node {
@@ -292,6 +800,7 @@ impl GemView {
                 }
                 GemtextNode::Blockquote(text) => {
                     let font = self.font_quote();
+                    let lang = self.lang_markup();
                     let (buf, mut iter) = self.get_iter();
                     let anchor = buf.create_child_anchor(&mut iter);
                     let quotebox = gtk::Box::builder()
@@ -307,17 +816,19 @@ impl GemView {
                     let label = gtk::Label::builder()
                         .selectable(true)
                         .use_markup(true)
+                        .wrap(true)
+                        .wrap_mode(pango::WrapMode::WordChar)
+                        .max_width_chars(1)
+                        .xalign(0.0)
                         .css_classes(vec!["blockquote".to_string()])
                         .label(&format!(
-                            "<span font=\"{font}\">{}</span>",
-                            self.wrap_text(
-                                &text,
-                                FontDescription::from_string(&self.font_paragraph()).size()
-                            ),
+                            "<span font=\"{font}\"{lang}>{}</span>",
+                            glib::markup_escape_text(&text),
                         ))
                         .build();
                     quotebox.append(&label);
-                    self.add_child_at_anchor(&quotebox, &anchor);
+                    self.insert_widget(&quotebox, &anchor);
+                    self.set_anchor_direction(&quotebox, &anchor, &text);
                     iter = buf.end_iter();
                     buf.insert(&mut iter, "\n");
                 }
@@ -334,18 +845,19 @@ impl GemView {
                         .build();
                     let (buf, mut iter) = self.get_iter();
                     let anchor = buf.create_child_anchor(&mut iter);
-                    self.add_child_at_anchor(&prebox, &anchor);
                     let font = self.font_pre();
+                    let lang = self.lang_markup();
                     let label = gtk::Label::builder()
                         .selectable(true)
                         .use_markup(true)
                         .css_classes(vec!["preformatted".to_string()])
                         .label(&format!(
-                            "<span font=\"{font}\">{}</span>",
+                            "<span font=\"{font}\"{lang}>{}</span>",
                             glib::markup_escape_text(&text)
                         ))
                         .build();
                     prebox.append(&label);
+                    self.insert_widget(&prebox, &anchor);
                     iter = buf.end_iter();
                     buf.insert(&mut iter, "\n");
                 }
@@ -353,6 +865,254 @@ impl GemView {
         }
     }
 
+    /// Applies the "page-language" property to the text of the page, so that
+    /// Pango can select the correct shaping and line breaking rules, and sets
+    /// a right to left base direction for languages written that way
+    fn apply_page_language(&self) {
+        let lang = self.page_language();
+        let primary = lang.split(',').next().unwrap_or_default().trim();
+        for tag in [
+            self.paragraph_tag(),
+            self.h1_tag(),
+            self.h2_tag(),
+            self.h3_tag(),
+        ] {
+            if primary.is_empty() {
+                tag.set_property("language-set", false);
+            } else {
+                tag.set_language(Some(primary));
+            }
+        }
+        self.set_direction(if bidi::is_rtl_language(primary) {
+            gtk::TextDirection::Rtl
+        } else {
+            gtk::TextDirection::None
+        });
+    }
+
+    /// Returns a Pango markup `lang` attribute for the current page language,
+    /// or an empty string if the language is unknown
+    fn lang_markup(&self) -> String {
+        let lang = self.page_language();
+        match lang.split(',').next().map(str::trim) {
+            Some(l) if !l.is_empty() => {
+                format!(" lang=\"{}\"", glib::markup_escape_text(l))
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Adds a widget to the page at `anchor`, scaled by the "zoom" property.
+    /// The widget should be complete, as children added afterwards are not
+    /// scaled until the zoom next changes.
+    fn insert_widget(&self, widget: &impl IsA<gtk::Widget>, anchor: &gtk::TextChildAnchor) {
+        self.add_child_at_anchor(widget, anchor);
+        self.style_widget(widget.upcast_ref());
+        Self::zoom_widget(widget.upcast_ref(), self.zoom());
+        Self::fit_widget(widget.upcast_ref(), self.imp().content_width.get());
+    }
+
+    /// Gets the "max-content-width" property in pixels, measuring characters
+    /// of the paragraph font at the current zoom, or `None` if the width of
+    /// the text column is not limited
+    fn max_content_pixels(&self) -> Option<i32> {
+        let max = i32::try_from(self.max_content_width()).unwrap_or(i32::MAX);
+        if max == 0 {
+            None
+        } else if self.content_width_in_chars() {
+            let font = FontDescription::from_string(&self.font_paragraph());
+            let metrics = self.pango_context().metrics(Some(&font), None);
+            let char_width = f64::from(metrics.approximate_char_width()) / f64::from(pango::SCALE);
+            #[allow(clippy::cast_possible_truncation)]
+            Some((char_width * self.zoom() * f64::from(max)) as i32)
+        } else {
+            Some(max)
+        }
+    }
+
+    /// Lays out the text column for the current width of the view, centering
+    /// it between margins of at least the "page-margin" property, and resizes
+    /// wrapping blocks such as links and blockquotes to fit the new column
+    fn update_text_column(&self) {
+        let width = self.width();
+        if width <= 0 {
+            return;
+        }
+        let margin = self.page_margin();
+        let mut column = width - 2 * margin;
+        if let Some(max) = self.max_content_pixels() {
+            column = column.min(max);
+        }
+        let column = column.max(1);
+        let side = ((width - column) / 2).max(0);
+        if self.left_margin() != side {
+            self.set_left_margin(side);
+        }
+        if self.right_margin() != side {
+            self.set_right_margin(side);
+        }
+        if self.imp().content_width.replace(column) != column {
+            let mut child = self.first_child();
+            while let Some(c) = child {
+                Self::fit_widget(&c, column);
+                child = c.next_sibling();
+            }
+        }
+    }
+
+    /// Gives blocks which wrap their text the width of the text column, less
+    /// their own margins, so that GTK can wrap them. Other widgets keep their
+    /// natural size.
+    fn fit_widget(widget: &gtk::Widget, column: i32) {
+        if column > 0 && (widget.has_css_class("blockquote") || widget.has_css_class("link")) {
+            let width = column - widget.margin_start() - widget.margin_end();
+            widget.set_size_request(width.max(1), -1);
+        }
+    }
+
+    /// Inserts Pango markup at `iter`, scaled by the "zoom" property and
+    /// colored by `tag`
+    fn insert_scaled_markup(&self, iter: &mut gtk::TextIter, markup: &str, tag: &gtk::TextTag) {
+        let buf = self.buffer();
+        let start = iter.offset();
+        buf.insert_markup(iter, markup);
+        let start = buf.iter_at_offset(start);
+        buf.apply_tag(&self.zoom_tag(), &start, iter);
+        buf.apply_tag(tag, &start, iter);
+    }
+
+    /// Gets a tag which colors text inserted as markup, either "quote" or
+    /// "preformatted". Its color is set from the theme.
+    fn theme_tag(&self, name: &str) -> gtk::TextTag {
+        let buf = self.buffer();
+        buf.tag_table()
+            .lookup(name)
+            .unwrap_or_else(|| buf.create_tag(Some(name), &[]).unwrap())
+    }
+
+    /// Adds the theme stylesheet to a widget and all of its children. This is
+    /// needed for every widget added to the page, as a stylesheet added to the
+    /// view does not reach its children.
+    fn style_widget(&self, widget: &gtk::Widget) {
+        #[allow(deprecated)]
+        widget
+            .style_context()
+            .add_provider(&self.imp().css, THEME_PRIORITY);
+        let mut child = widget.first_child();
+        while let Some(c) = child {
+            self.style_widget(&c);
+            child = c.next_sibling();
+        }
+    }
+
+    /// Gets the tag which scales text inserted as markup, which is not covered
+    /// by the paragraph and heading tags
+    fn zoom_tag(&self) -> gtk::TextTag {
+        let buf = self.buffer();
+        buf.tag_table().lookup("zoom").unwrap_or_else(|| {
+            buf.create_tag(Some("zoom"), &[("scale", &self.zoom())])
+                .unwrap()
+        })
+    }
+
+    /// Multiplies the "zoom" property by a step, up to a maximum of 4
+    pub fn zoom_in(&self) {
+        self.set_zoom((self.zoom() * ZOOM_STEP).min(4.0));
+    }
+
+    /// Divides the "zoom" property by a step, down to a minimum of 0.25
+    pub fn zoom_out(&self) {
+        self.set_zoom((self.zoom() / ZOOM_STEP).max(0.25));
+    }
+
+    /// Returns the "zoom" property to its normal size
+    pub fn zoom_reset(&self) {
+        self.set_zoom(1.0);
+    }
+
+    /// Scales all of the content on the page by the "zoom" property, without
+    /// reloading it
+    fn apply_zoom(&self) {
+        let zoom = self.zoom();
+        for tag in [
+            self.paragraph_tag(),
+            self.h1_tag(),
+            self.h2_tag(),
+            self.h3_tag(),
+            self.zoom_tag(),
+        ] {
+            tag.set_scale(zoom);
+        }
+        let mut child = self.first_child();
+        while let Some(c) = child {
+            Self::zoom_widget(&c, zoom);
+            child = c.next_sibling();
+        }
+        // Animations are scaled in place so that playback carries on, while
+        // static images are part of the text rather than child widgets, and
+        // are rendered again at the new size
+        let animation = self.imp().animation.borrow().clone();
+        if let Some(animation) = animation {
+            animation.set_width(self.image_width());
+        } else if self.buffer_mime().starts_with("image") && !self.imp().input_form.get() {
+            self.render_image_from_bytes(&self.buffer_content());
+        }
+    }
+
+    /// Scales a widget embedded in the page and all of its children by scaling
+    /// the font of each label. Animations are resized separately, by scaling
+    /// their frames to the zoomed width of the view.
+    fn zoom_widget(widget: &gtk::Widget, zoom: f64) {
+        if let Some(label) = widget.downcast_ref::<gtk::Label>() {
+            let attrs = pango::AttrList::new();
+            attrs.insert(pango::AttrFloat::new_scale(zoom));
+            label.set_attributes(Some(&attrs));
+        }
+        let mut child = widget.first_child();
+        while let Some(c) = child {
+            Self::zoom_widget(&c, zoom);
+            child = c.next_sibling();
+        }
+    }
+
+    /// Gets a tag setting the base direction of a line to match its text, so
+    /// that right to left lines are aligned to the right along with their
+    /// list bullets. Returns `None` if the text has no strongly directional
+    /// characters, in which case the line follows the page direction.
+    fn direction_tag(&self, text: &str) -> Option<gtk::TextTag> {
+        let direction = bidi::base_direction(text)?;
+        let name = match direction {
+            gtk::TextDirection::Rtl => "rtl",
+            _ => "ltr",
+        };
+        let buf = self.buffer();
+        buf.tag_table()
+            .lookup(name)
+            .or_else(|| buf.create_tag(Some(name), &[("direction", &direction)]))
+    }
+
+    /// Sets the direction of a widget embedded in the page to match its text,
+    /// and gives the line holding its anchor the same base direction so that
+    /// the widget is placed on the matching side of the page
+    fn set_anchor_direction(
+        &self,
+        widget: &impl IsA<gtk::Widget>,
+        anchor: &gtk::TextChildAnchor,
+        text: &str,
+    ) {
+        let Some(tag) = self.direction_tag(text) else {
+            return;
+        };
+        if let Some(direction) = bidi::base_direction(text) {
+            widget.set_direction(direction);
+        }
+        let buf = self.buffer();
+        let start = buf.iter_at_child_anchor(anchor);
+        let mut end = start;
+        end.forward_char();
+        buf.apply_tag(&tag, &start, &end);
+    }
+
     fn insert_text_block(&self, text: &str, size: TextSize) {
         let (buf, mut iter) = self.get_iter();
         let tag = match size {
@@ -361,7 +1121,10 @@ impl GemView {
             TextSize::H2 => self.imp().h2_tag.borrow(),
             TextSize::H3 => self.imp().h3_tag.borrow(),
         };
-        buf.insert_with_tags(&mut iter, text, &[&tag]);
+        match self.direction_tag(text) {
+            Some(dir) => buf.insert_with_tags(&mut iter, text, &[&tag, &dir]),
+            None => buf.insert_with_tags(&mut iter, text, &[&tag]),
+        }
         iter = buf.end_iter();
         buf.insert(&mut iter, "\n");
     }
@@ -369,181 +1132,1007 @@ impl GemView {
     fn insert_list_item(&self, text: &str) {
         let (buf, mut iter) = self.get_iter();
         let tag = self.imp().paragraph_tag.borrow();
-        buf.insert_with_tags(&mut iter, &format!("  • {text}"), &[&tag]);
+        let item = format!("  • {text}");
+        match self.direction_tag(text) {
+            Some(dir) => buf.insert_with_tags(&mut iter, &item, &[&tag, &dir]),
+            None => buf.insert_with_tags(&mut iter, &item, &[&tag]),
+        }
+        iter = buf.end_iter();
+        buf.insert(&mut iter, "\n");
+    }
+
+    fn insert_link(&self, link: &str, text: Option<String>) {
+        let key = linkicon::icon_key(link, &self.uri());
+        let title = text.clone().unwrap_or_else(|| link.to_string());
+        let label = self.insert_gmi_link_markup_label(&key, link, text, LinkKind::Link);
+        label.set_extra_menu(Some(&Self::context_menu(link, &title)));
+        self.on_link_activated(&label, Self::visit);
+    }
+
+    fn insert_prompt_link(&self, link: &str, text: Option<String>) {
+        match self.uri().split_once(':') {
+            Some((s, _)) if s == "spartan" => {
+                let label =
+                    self.insert_gmi_link_markup_label("upload", link, text, LinkKind::Prompt);
+                self.on_link_activated(&label, |viewer, url| {
+                    viewer.set_uri(url);
+                    viewer.emit_by_name::<()>("request-upload", &[&url]);
+                });
+            }
+            _ => {
+                let text = if let Some(t) = text {
+                    Cow::from(format!("=: {link} {t}"))
+                } else {
+                    Cow::from(link)
+                };
+                self.insert_text_block(&text, TextSize::Paragraph);
+            }
+        }
+    }
+
+    fn insert_gmi_link_markup_label(
+        &self,
+        key: &str,
+        link: &str,
+        text: Option<String>,
+        kind: LinkKind,
+    ) -> gtk::Label {
+        let display = text.unwrap_or_else(|| link.to_string());
+        let url = link;
+        let link = link.replace('&', "&amp;");
+        let label = gtk::Label::builder()
+            .use_markup(true)
+            .wrap(true)
+            .wrap_mode(pango::WrapMode::WordChar)
+            .max_width_chars(1)
+            .hexpand(true)
+            .xalign(0.0)
+            .label(format!(
+                "<span font=\"{}\"{}><a href=\"{}\">{}</a></span>",
+                self.font_paragraph(),
+                self.lang_markup(),
+                &link,
+                glib::markup_escape_text(&display),
+            ))
+            .build();
+        label.set_cursor_from_name(Some("pointer"));
+        self.insert_link_label(&label, key, url, &display, kind);
+        label
+    }
+
+    #[must_use]
+    /// Gets the icon shown in front of links with the given key. The key is
+    /// the url scheme, such as "gemini" or "https", except that gopher links
+    /// are keyed by item type as "gopher:" followed by the type character, eg
+    /// "gopher:1" for menus, and spartan upload prompts use "upload".
+    pub fn link_icon(&self, key: &str) -> LinkIcon {
+        if let Some(icon) = self.imp().link_icons.borrow().get(key) {
+            return icon.clone();
+        }
+        match key.strip_prefix("gopher:") {
+            Some(_) => match self.imp().link_icons.borrow().get("gopher") {
+                Some(icon) => icon.clone(),
+                None => linkicon::default_icon(key),
+            },
+            None => linkicon::default_icon(key),
+        }
+    }
+
+    /// Sets the icon shown in front of links with the given key, as described
+    /// for [`GemView::link_icon`]. An icon set for the "gopher" key applies to
+    /// every gopher item type without its own icon. Passing `None` restores
+    /// the default icon. Takes effect for pages loaded afterwards.
+    pub fn set_link_icon(&self, key: &str, icon: Option<LinkIcon>) {
+        let mut icons = self.imp().link_icons.borrow_mut();
+        match icon {
+            Some(i) => icons.insert(key.to_string(), i),
+            None => icons.remove(key),
+        };
+    }
+
+    /// Builds the widget shown in front of a link, or `None` if its icon is
+    /// hidden
+    fn link_icon_widget(&self, key: &str) -> Option<gtk::Widget> {
+        match self.link_icon(key) {
+            LinkIcon::Text(text) => {
+                let class = theme::icon_class(linkicon::icon_scheme(key));
+                let label = gtk::Label::builder()
+                    .label(text)
+                    .css_classes(vec!["link-icon".to_string(), class.to_string()])
+                    .build();
+                Some(label.upcast())
+            }
+            LinkIcon::Named(name) => Some(gtk::Image::from_icon_name(&name).upcast()),
+            LinkIcon::Hidden => None,
+        }
+    }
+
+    /// Inserts a link at the end of the page, preceded by its icon, and adds it
+    /// to the links of the page. This is shared by gemtext and gopher links.
+    fn insert_link_label(
+        &self,
+        label: &gtk::Label,
+        key: &str,
+        url: &str,
+        display: &str,
+        kind: LinkKind,
+    ) {
+        let (buf, mut iter) = self.get_iter();
+        let anchor = buf.create_child_anchor(&mut iter);
+        let linkbox = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(8)
+            .margin_start(4)
+            .css_classes(vec!["link".to_string()])
+            .build();
+        if let Some(icon) = self.link_icon_widget(key) {
+            linkbox.append(&icon);
+        }
+        linkbox.append(label);
+        self.insert_widget(&linkbox, &anchor);
+        self.set_anchor_direction(&linkbox, &anchor, display);
+        let resolved = self
+            .join_url(url)
+            .map_or_else(|_| url.to_string(), String::from);
+        label.set_tooltip_text(Some(&Self::link_tooltip(&resolved)));
+        label.set_has_tooltip(self.link_tooltips());
+        let hover = gtk::EventControllerMotion::new();
+        let viewer = self.clone();
+        let hovered = resolved.clone();
+        hover.connect_enter(move |_, _, _| {
+            viewer.imp().link_hovered.set(true);
+            viewer.emit_by_name::<()>("link-hovered", &[&hovered]);
+        });
+        let viewer = self.clone();
+        hover.connect_leave(move |_| viewer.unhover_link());
+        label.add_controller(hover);
+        // Record how the link is about to be activated, as the "activate-link"
+        // signal does not say. Clicks which open the link in a new tab or
+        // window are claimed, so that the label does not also follow it.
+        let click = gtk::GestureClick::new();
+        click.set_button(0);
+        click.set_propagation_phase(gtk::PropagationPhase::Capture);
+        let elsewhere = Rc::new(Cell::new(None));
+        let viewer = self.clone();
+        let opens_page = kind.opens_page();
+        let pressed = elsewhere.clone();
+        click.connect_pressed(move |gesture, _, _, _| {
+            let modifiers = gesture.current_event_state();
+            let target = match gesture.current_button() {
+                gtk::gdk::BUTTON_MIDDLE => Some(NavigationTrigger::NewTab),
+                gtk::gdk::BUTTON_PRIMARY if modifiers.contains(ModifierType::CONTROL_MASK) => {
+                    Some(NavigationTrigger::NewTab)
+                }
+                gtk::gdk::BUTTON_PRIMARY if modifiers.contains(ModifierType::SHIFT_MASK) => {
+                    Some(NavigationTrigger::NewWindow)
+                }
+                _ => None,
+            }
+            .filter(|_| opens_page);
+            if target.is_some() {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+            } else {
+                // The label only activates the link for presses which are not
+                // claimed here
+                viewer
+                    .imp()
+                    .pending_navigation
+                    .set(Some((NavigationTrigger::Click, modifiers)));
+            }
+            pressed.set(target.map(|t| (t, modifiers)));
+        });
+        let viewer = self.clone();
+        let url = resolved.clone();
+        click.connect_released(move |_, _, _, _| {
+            if let Some((trigger, modifiers)) = elsewhere.take() {
+                viewer.open_elsewhere(&url, trigger, modifiers);
+            }
+        });
+        label.add_controller(click);
+        let viewer = self.clone();
+        label.connect_activate_current_link(move |_| {
+            viewer
+                .imp()
+                .pending_navigation
+                .set(Some((NavigationTrigger::Keyboard, ModifierType::empty())));
+        });
+        let mut links = self.imp().links.borrow_mut();
+        let index = links.len();
+        links.push(RenderedLink {
+            link: PageLink {
+                index,
+                url: resolved,
+                label: display.to_string(),
+                kind,
+                line: 0,
+            },
+            href: url.to_string(),
+            label: label.clone(),
+            anchor,
+        });
         iter = buf.end_iter();
         buf.insert(&mut iter, "\n");
     }
 
-    fn insert_link(&self, link: &str, text: Option<String>) {
-        let u = self.uri();
-        let (old, _) = u.split_once(':').unwrap_or(("gemini", ""));
-        let (scheme, _) = link.split_once(':').unwrap_or((old, ""));
-        let start = match scheme {
-            "gemini" => "<span color=\"#0000ff\"> 🛰️  </span>",
-            "spartan" => "<span color=\"#0000ff\"> 🗡️ </span>",
-            "gopher" => "<span color=\"#00ff00\"> 🕳️  </span>",
-            "finger" => "<span color=\"#00ffff\"> 👉 </span>",
-            "data" => "<span color=\"#ff00ff\"> 📊 </span>",
-            "http" | "https" => "<span color=\"#ff0000\"> 🌐  </span>",
-            "mailto" => "<span color=\"#ffff00\"> ✉️ </span>",
-            "file" => "<span color=\"#0000ff\"> 🗄️ </span>",
-            _ => "<span color=\"#ffff00\"> 🌐  </span>",
-        };
-        let label = self.insert_gmi_link_markup_label(start, link, text);
-        label.set_extra_menu(Some(&Self::context_menu(link)));
+    /// Opens a link in a new tab or window, according to `trigger`, by
+    /// emitting the "request-new-tab" or "request-new-window" signal with the
+    /// absolute url once the "decide-navigation" signal has allowed it. The
+    /// scheme is only checked after that, so that handlers see every link.
+    fn open_elsewhere(&self, url: &str, trigger: NavigationTrigger, modifiers: ModifierType) {
+        let signal = match trigger {
+            NavigationTrigger::NewWindow => "request-new-window",
+            _ => "request-new-tab",
+        };
+        let url = self
+            .join_url(url)
+            .map_or_else(|_| url.to_string(), String::from);
+        if let Some(url) = self.decide_navigation(&url, trigger, modifiers) {
+            if let Ok(url) = self.absolute_url(&url) {
+                self.emit_by_name::<()>(signal, &[&url.as_str()]);
+            }
+        }
+    }
+
+    /// Connects the action taken when a link is followed. The url is resolved
+    /// against the current page and passed through the "decide-navigation"
+    /// signal first, and the action is given the url which was decided on.
+    fn on_link_activated<F: Fn(&Self, &str) + 'static>(&self, label: &gtk::Label, f: F) {
+        let viewer = self.clone();
+        label.connect_activate_link(move |_, link| {
+            let (trigger, modifiers) = viewer
+                .imp()
+                .pending_navigation
+                .take()
+                .unwrap_or((NavigationTrigger::Click, ModifierType::empty()));
+            let url = viewer
+                .join_url(link)
+                .map_or_else(|_| link.to_string(), String::from);
+            if let Some(url) = viewer.decide_navigation(&url, trigger, modifiers) {
+                f(&viewer, &url);
+            }
+            gtk::Inhibit(true)
+        });
+    }
+
+    /// Emits the "decide-navigation" signal for a link which is about to be
+    /// followed, returning the url to follow or `None` if a handler denied it.
+    /// The link is followed unchanged if no handler decides.
+    fn decide_navigation(
+        &self,
+        url: &str,
+        trigger: NavigationTrigger,
+        modifiers: ModifierType,
+    ) -> Option<String> {
+        match self
+            .emit_by_name::<Option<String>>("decide-navigation", &[&url, &trigger, &modifiers])
+        {
+            None => Some(url.to_string()),
+            Some(u) if u.is_empty() => None,
+            Some(u) => Some(u),
+        }
+    }
+
+    /// Emits the "link-unhovered" signal if the pointer was over a link. This
+    /// is also done when the page is cleared, as a link removed from beneath
+    /// the pointer is never left.
+    fn unhover_link(&self) {
+        if self.imp().link_hovered.replace(false) {
+            self.emit_by_name::<()>("link-unhovered", &[]);
+        }
+    }
+
+    /// Shortens a url to at most 80 characters for display in a tooltip
+    fn link_tooltip(url: &str) -> String {
+        match url.char_indices().nth(80) {
+            Some((end, _)) => format!("{}...", &url[..end]),
+            None => url.to_string(),
+        }
+    }
+
+    /// Shows or hides the tooltips of the links on the current page, following
+    /// the "link-tooltips" property
+    fn apply_link_tooltips(&self) {
+        let show = self.link_tooltips();
+        for link in self.imp().links.borrow().iter() {
+            link.label.set_has_tooltip(show);
+        }
+    }
+
+    #[must_use]
+    /// Lists every link on the current page, in the order they appear. The
+    /// "links-changed" signal is emitted whenever the list changes. Only
+    /// lines rendered as links are listed, so gopher search items shown as
+    /// search fields through the "inline-input" property are left out, as
+    /// are `=:` prompt lines outside of spartan pages, which are shown as
+    /// plain text.
+    pub fn links(&self) -> Vec<PageLink> {
+        let buf = self.buffer();
+        self.imp()
+            .links
+            .borrow()
+            .iter()
+            .map(|l| PageLink {
+                line: buf.iter_at_child_anchor(&l.anchor).line(),
+                ..l.link.clone()
+            })
+            .collect()
+    }
+
+    /// Emits the "links-changed" signal once the page being rendered is
+    /// complete. Pages are rendered without returning to the main loop, so
+    /// this happens once per page however many links it has.
+    fn queue_links_changed(&self) {
+        if !self.imp().links_changed_pending.replace(true) {
+            let viewer = self.downgrade();
+            glib::idle_add_local_once(move || {
+                if let Some(viewer) = viewer.upgrade() {
+                    viewer.imp().links_changed_pending.set(false);
+                    viewer.emit_by_name::<()>("links-changed", &[]);
+                }
+            });
+        }
+    }
+
+    /// Follows the link with the given index, as listed by [`GemView::links`],
+    /// exactly as if it had been clicked except that the "decide-navigation"
+    /// signal is given [`NavigationTrigger::Api`]
+    pub fn follow_link(&self, index: usize) {
+        self.activate_link(index, NavigationTrigger::Api);
+    }
+
+    fn activate_link(&self, index: usize, trigger: NavigationTrigger) {
+        let link = self
+            .imp()
+            .links
+            .borrow()
+            .get(index)
+            .map(|l| (l.label.clone(), l.href.clone()));
+        if let Some((label, url)) = link {
+            self.imp()
+                .pending_navigation
+                .set(Some((trigger, ModifierType::empty())));
+            label.emit_by_name::<bool>("activate-link", &[&url]);
+        }
+    }
+
+    /// Moves the keyboard focus to the link following the focused one,
+    /// scrolling it into view. The first link is focused if none has focus.
+    pub fn focus_next_link(&self) {
+        let count = self.imp().links.borrow().len();
+        if count > 0 {
+            let index = self.focused_link().map_or(0, |i| (i + 1) % count);
+            self.focus_link(index);
+        }
+    }
+
+    /// Moves the keyboard focus to the link preceding the focused one,
+    /// scrolling it into view. The last link is focused if none has focus.
+    pub fn focus_previous_link(&self) {
+        let count = self.imp().links.borrow().len();
+        if count > 0 {
+            let index = self
+                .focused_link()
+                .map_or(count - 1, |i| (i + count - 1) % count);
+            self.focus_link(index);
+        }
+    }
+
+    /// Gets the index of the link which has the keyboard focus
+    fn focused_link(&self) -> Option<usize> {
+        self.imp()
+            .links
+            .borrow()
+            .iter()
+            .position(|l| l.label.has_focus())
+    }
+
+    fn focus_link(&self, index: usize) {
+        let link = self
+            .imp()
+            .links
+            .borrow()
+            .get(index)
+            .map(|l| (l.label.clone(), l.anchor.clone()));
+        if let Some((label, anchor)) = link {
+            let mut iter = self.buffer().iter_at_child_anchor(&anchor);
+            self.scroll_to_iter(&mut iter, 0.1, false, 0.0, 0.0);
+            // Entering the label this way selects its link, so that it can be
+            // followed with the Enter key
+            if !label.child_focus(gtk::DirectionType::TabForward) {
+                label.grab_focus();
+            }
+        }
+    }
+
+    /// Overlays a short hint on each link currently in view. Typing the keys
+    /// of a hint follows that link, while Escape or any key not matching a
+    /// hint removes them.
+    pub fn show_link_hints(&self) {
+        self.hide_link_hints();
+        #[allow(clippy::cast_precision_loss)]
+        let height = self.height() as f32;
+        let visible = self
+            .imp()
+            .links
+            .borrow()
+            .iter()
+            .filter_map(|l| {
+                let bounds = l.label.compute_bounds(self)?;
+                (bounds.y() + bounds.height() > 0.0 && bounds.y() < height).then(|| {
+                    #[allow(clippy::cast_possible_truncation)]
+                    (l.link.index, bounds.x() as i32, bounds.y() as i32)
+                })
+            })
+            .collect::<Vec<_>>();
+        let hints = pagelink::hint_keys(visible.len())
+            .into_iter()
+            .zip(visible)
+            .map(|(keys, (index, x, y))| {
+                let hint = gtk::Label::builder()
+                    .label(format!(" {keys} "))
+                    .css_classes(vec!["link-hint".to_string()])
+                    .build();
+                self.style_widget(hint.upcast_ref());
+                let (x, y) = self.window_to_buffer_coords(gtk::TextWindowType::Widget, x, y);
+                self.add_overlay(&hint, x, y);
+                LinkHint {
+                    keys,
+                    index,
+                    widget: hint.upcast(),
+                }
+            })
+            .collect();
+        *self.imp().link_hints.borrow_mut() = hints;
+    }
+
+    /// Removes any link hints from the page
+    pub fn hide_link_hints(&self) {
+        self.imp().hint_input.borrow_mut().clear();
+        for hint in self.imp().link_hints.take() {
+            self.remove(&hint.widget);
+        }
+    }
+
+    /// Handles a key press while link hints are shown, returning `true` if
+    /// the key was used
+    fn link_hint_key(&self, key: gtk::gdk::Key) -> bool {
+        if self.imp().link_hints.borrow().is_empty() {
+            return false;
+        }
+        if key == gtk::gdk::Key::Escape {
+            self.hide_link_hints();
+            return true;
+        }
+        let Some(c) = key.to_unicode().filter(char::is_ascii_alphabetic) else {
+            return false;
+        };
+        let input = {
+            let mut input = self.imp().hint_input.borrow_mut();
+            input.push(c.to_ascii_lowercase());
+            input.clone()
+        };
+        let (chosen, partial) = {
+            let hints = self.imp().link_hints.borrow();
+            for hint in hints.iter() {
+                hint.widget.set_visible(hint.keys.starts_with(&input));
+            }
+            (
+                hints.iter().find(|h| h.keys == input).map(|h| h.index),
+                hints.iter().any(|h| h.keys.starts_with(&input)),
+            )
+        };
+        if chosen.is_some() || !partial {
+            self.hide_link_hints();
+        }
+        if let Some(index) = chosen {
+            self.activate_link(index, NavigationTrigger::Keyboard);
+        }
+        true
+    }
+
+    /// Renders a `GopherMap`. Each item type is given its own icon, as
+    /// described for [`GemView::link_icon`]. Telnet
+    /// items are passed to the "request-unsupported-scheme" signal as a
+    /// `telnet://` url, while CSO and search items prompt for a query. If the
+    /// "inline-input" property is set, search items are rendered as a search
+    /// field.
+    fn render_gopher(&self, content: &Content) {
+        self.clear();
+        let plus_items = gopherplus::plus_items(&String::from_utf8_lossy(&content.bytes));
+        let mut plus_links = vec![];
+        for line in content.parse() {
+            match line {
+                gopher::parser::LineType::Text(text) => {
+                    self.insert_gopher_text(&text);
+                }
+                gopher::parser::LineType::Link(link) => {
+                    let url = link.to_string();
+                    let kind = ItemType::from_url(&url);
+                    if kind.is_text_line() {
+                        self.insert_gopher_text(&link.display);
+                        continue;
+                    }
+                    let label = link.to_label(&FontDescription::from_string(&self.font_pre()));
+                    self.insert_link_label(
+                        &label,
+                        &linkicon::icon_key(&url, ""),
+                        &url,
+                        &link.display,
+                        LinkKind::from_gopher_url(&url),
+                    );
+                    match kind {
+                        ItemType::Telnet | ItemType::Tn3270 => {
+                            self.on_link_activated(&label, |viewer, link| {
+                                if let Some(url) = itemtype::telnet_url(link) {
+                                    viewer
+                                        .emit_by_name::<()>("request-unsupported-scheme", &[&url]);
+                                }
+                            });
+                        }
+                        ItemType::Cso => {
+                            self.on_link_activated(&label, |viewer, link| {
+                                viewer.request_line_input("Enter CSO query", link, false);
+                            });
+                        }
+                        _ => {
+                            label.set_extra_menu(Some(&Self::context_menu(&url, &link.display)));
+                            self.on_link_activated(&label, Self::visit);
+                        }
+                    }
+                    if let Some(location) =
+                        Location::from_url(&url).filter(|l| plus_items.contains(l))
+                    {
+                        let buf = self.buffer();
+                        plus_links.push(PlusLink {
+                            location,
+                            url,
+                            display: link.display.to_string(),
+                            label,
+                            mark: buf.create_mark(None, &buf.end_iter(), true),
+                        });
+                    }
+                }
+                gopher::parser::LineType::Query(link) if self.inline_input() => {
+                    self.insert_input(&link.to_string(), &link.display, false);
+                    let (buf, mut iter) = self.get_iter();
+                    buf.insert(&mut iter, "\n");
+                }
+                gopher::parser::LineType::Query(link) => {
+                    let label = link.to_label(&FontDescription::from_string(&self.font_pre()));
+                    let url = link.to_string();
+                    let key = linkicon::icon_key(&url, "");
+                    self.insert_link_label(
+                        &label,
+                        &key,
+                        &url,
+                        &link.display,
+                        LinkKind::from_gopher_url(&url),
+                    );
+                    self.on_link_activated(&label, |viewer, link| {
+                        viewer.request_line_input("Enter query", link, false);
+                    });
+                }
+                gopher::parser::LineType::Http(link) => {
+                    let label = link.to_label(&FontDescription::from_string(&self.font_pre()));
+                    let key = linkicon::icon_key(&link.url, "");
+                    self.insert_link_label(
+                        &label,
+                        &key,
+                        &link.url,
+                        &link.display,
+                        LinkKind::Gopher('h'),
+                    );
+                    label.set_extra_menu(Some(&Self::context_menu(&link.url, &link.display)));
+                    self.on_link_activated(&label, Self::visit);
+                }
+            }
+        }
+        if !plus_links.is_empty() {
+            *self.imp().plus_links.borrow_mut() = plus_links;
+            let menu = self.uri();
+            let cached = self
+                .imp()
+                .gopher_attributes
+                .borrow()
+                .as_ref()
+                .filter(|(url, _)| *url == menu)
+                .map(|(_, attributes)| attributes.clone());
+            match cached {
+                Some(attributes) => self.insert_plus_attributes(&attributes),
+                None => self.fetch_gopher_attributes(menu),
+            }
+        }
+    }
+
+    /// Retrieves the Gopher+ attributes of the items in the current menu on a
+    /// background thread. The attributes of the whole menu are requested at
+    /// once, falling back to requesting the first few items individually if
+    /// the server does not support this. Those requests are made at the same
+    /// time, so that an unresponsive server delays the menu by a single
+    /// timeout. Attributes are displayed as they arrive.
+    fn fetch_gopher_attributes(&self, menu: String) {
+        let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
+        let urls = self
+            .imp()
+            .plus_links
+            .borrow()
+            .iter()
+            .map(|l| l.url.clone())
+            .collect::<Vec<_>>();
+        *self.imp().gopher_attributes.borrow_mut() = Some((menu.clone(), HashMap::new()));
+        let socks = self.socks_address();
+        let url = menu.clone();
+        thread::spawn(move || {
+            let attributes = request::gopher_plus(&url, "$", socks.as_deref())
+                .map(|d| gopherplus::parse_attributes(&String::from_utf8_lossy(&d)))
+                .unwrap_or_default();
+            if !attributes.is_empty() {
+                sender.send(attributes).expect("Cannot send data");
+                return;
+            }
+            for url in urls.into_iter().take(gopherplus::MAX_ITEM_REQUESTS) {
+                let sender = sender.clone();
+                let socks = socks.clone();
+                thread::spawn(move || {
+                    let attributes = request::gopher_plus(&url, "!", socks.as_deref())
+                        .map(|d| gopherplus::parse_attributes(&String::from_utf8_lossy(&d)))
+                        .unwrap_or_default();
+                    sender.send(attributes).expect("Cannot send data");
+                });
+            }
+        });
+        // The receiver is kept until every request has finished, even if the
+        // page changes. Attributes are only cached and displayed while the
+        // menu is still the current page, which may have been rendered again
+        // in the meantime.
+        let viewer = self.clone();
+        receiver.attach(None, move |attributes: HashMap<Location, Attributes>| {
+            let current = match viewer.imp().gopher_attributes.borrow_mut().as_mut() {
+                Some((url, cached)) if *url == menu => {
+                    cached.extend(attributes.clone());
+                    true
+                }
+                _ => false,
+            };
+            if current {
+                viewer.insert_plus_attributes(&attributes);
+            }
+            Continue(true)
+        });
+    }
+
+    /// Displays the attributes of the Gopher+ items on the current page which
+    /// are found in `attributes`
+    fn insert_plus_attributes(&self, attributes: &HashMap<Location, Attributes>) {
+        for link in self.imp().plus_links.borrow().iter() {
+            if let Some(a) = attributes.get(&link.location) {
+                self.insert_gopher_attributes(link, a);
+            }
+        }
+    }
+
+    /// Displays an item's abstract below its link, adds the administrative
+    /// attributes to the tooltip and any alternate views to the context menu
+    fn insert_gopher_attributes(&self, link: &PlusLink, attributes: &Attributes) {
+        if !attributes.abstract_.is_empty() {
+            let buf = self.buffer();
+            let mut iter = buf.iter_at_mark(&link.mark);
+            let text = attributes
+                .abstract_
+                .iter()
+                .map(|l| format!("    {l}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            self.insert_scaled_markup(
+                &mut iter,
+                &format!(
+                    "<span font=\"{}\">{}</span>\n",
+                    &self.font_pre(),
+                    glib::markup_escape_text(&text)
+                ),
+                &self.theme_tag("quote"),
+            );
+        }
+        if !attributes.admin.is_empty() {
+            let tooltip = format!(
+                "{}\n{}",
+                Self::link_tooltip(&link.url),
+                attributes.admin.join("\n")
+            );
+            link.label.set_tooltip_text(Some(&tooltip));
+            link.label.set_has_tooltip(self.link_tooltips());
+        }
+        if !attributes.views.is_empty() {
+            let menu = Self::context_menu(&link.url, &link.display);
+            for view in &attributes.views {
+                let action_name = format!(
+                    "viewer.gopher-view(('{}','{}'))",
+                    urlencoding::encode(&link.url),
+                    urlencoding::encode(view)
+                );
+                let item = MenuItem::new(Some(&format!("View as {view}")), Some(&action_name));
+                menu.append_item(&item);
+            }
+            link.label.set_extra_menu(Some(&menu));
+        }
+    }
+
+    /// Requests an alternate view of a Gopher+ item. The view is given as it
+    /// appears in the item's `+VIEWS` attribute block, eg "text/plain En_US".
+    pub fn visit_gopher_view(&self, url: &str, view: &str) {
+        self.emit_by_name::<()>("page-load-started", &[&url]);
+        let Ok(parsed) = Url::parse(url) else {
+            let estr = format!("Invalid url: {url}");
+            self.emit_by_name::<()>("page-load-failed", &[&estr]);
+            return;
+        };
+        let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
+        let req = url.to_string();
+        let mime = view
+            .split_whitespace()
+            .next()
+            .unwrap_or("text/plain")
+            .to_lowercase();
+        let command = format!("+{view}");
+        let socks = self.socks_address();
+        thread::spawn(move || {
+            let response = match request::gopher_plus(&req, &command, socks.as_deref()) {
+                Ok(bytes) => Response::Success(Content {
+                    url: Some(req),
+                    mime,
+                    bytes,
+                }),
+                Err(e) => Response::Error(e),
+            };
+            sender.send(response).expect("Cannot send data");
+        });
+        let viewer = self.clone();
+        receiver.attach(None, move |response| {
+            match response {
+                Response::Success(content) if content.mime == "application/gopher-menu" => {
+                    let url = parsed.to_string();
+                    viewer.append_history(&url);
+                    viewer.set_buffer_mime(&content.mime);
+                    viewer.set_buffer_content(&content.bytes);
+                    let text = viewer.decode_text(&content.bytes, None);
+                    viewer.render_gopher(&Content {
+                        bytes: text.into_bytes(),
+                        ..content
+                    });
+                    viewer.emit_by_name::<()>("page-loaded", &[&url]);
+                }
+                Response::Success(content) => {
+                    viewer.process_gemini_response_success(&content, &parsed);
+                }
+                Response::Error(err) => {
+                    viewer.emit_by_name::<()>("page-load-failed", &[&err]);
+                }
+                _ => unreachable!(),
+            }
+            Continue(false)
+        });
+    }
+
+    /// Requests input from the user for a gemini `1x` prompt. If the
+    /// "multiline-input" property is set the page is replaced with a text
+    /// editor, otherwise this is the same as [`GemView::request_line_input`].
+    /// Sensitive input always uses a masked single line entry.
+    fn request_input(&self, meta: &str, url: &str, sensitive: bool) {
+        if self.multiline_input() && !sensitive {
+            self.show_input_form(meta);
+            let editor = self.insert_multiline_input(url);
+            editor.grab_focus();
+        } else {
+            self.request_line_input(meta, url, sensitive);
+        }
+    }
+
+    /// Requests a single line of input from the user for the given url. This
+    /// is used directly for gopher searches and CSO queries, as the byte limit
+    /// shown by the multi line editor applies only to gemini. If the
+    /// "inline-input" or "multiline-input" property is set the page is replaced
+    /// with an entry. Otherwise the "request-input" or
+    /// "request-input-sensitive" signal is emitted.
+    fn request_line_input(&self, meta: &str, url: &str, sensitive: bool) {
+        if self.inline_input() || self.multiline_input() {
+            self.show_input_form(meta);
+            let entry = self.insert_input(url, "", sensitive);
+            entry.grab_focus();
+        } else {
+            let signal = if sensitive {
+                "request-input-sensitive"
+            } else {
+                "request-input"
+            };
+            self.emit_by_name::<()>(signal, &[&meta, &url]);
+        }
+    }
+
+    /// Replaces the page with the prompt of an input form. The source of the
+    /// previous page is kept in the buffer, and is not rendered again while
+    /// the form is shown.
+    fn show_input_form(&self, meta: &str) {
+        self.clear();
+        self.imp().input_form.set(true);
+        self.insert_text_block(meta, TextSize::Paragraph);
+    }
+
+    /// Inserts a text entry which, when activated, visits `url` with the
+    /// percent encoded input as the query. For gemini urls the number of bytes
+    /// remaining before the 1024 byte limit on the request is shown, and
+    /// input beyond the limit cannot be submitted.
+    fn insert_input(&self, url: &str, placeholder: &str, sensitive: bool) -> gtk::Widget {
+        let (buf, mut iter) = self.get_iter();
+        let inputbox = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .hexpand(true)
+            .halign(gtk::Align::Fill)
+            .spacing(4)
+            .margin_bottom(8)
+            .margin_top(8)
+            .css_classes(vec!["input".to_string()])
+            .build();
+        let entry: gtk::Widget = if sensitive {
+            gtk::PasswordEntry::builder()
+                .show_peek_icon(true)
+                .placeholder_text(placeholder)
+                .hexpand(true)
+                .build()
+                .upcast()
+        } else {
+            gtk::Entry::builder()
+                .placeholder_text(placeholder)
+                .hexpand(true)
+                .build()
+                .upcast()
+        };
+        let submit = gtk::Button::with_label("Submit");
+        let row = gtk::Box::builder()
+            .orientation(gtk::Orientation::Horizontal)
+            .spacing(4)
+            .build();
+        row.append(&entry);
+        row.append(&submit);
+        inputbox.append(&row);
+        let base = Self::input_base(url);
+        if base.starts_with("gemini:") {
+            let counter = gtk::Label::builder()
+                .halign(gtk::Align::End)
+                .css_classes(vec!["dim-label".to_string()])
+                .build();
+            inputbox.append(&counter);
+            let update = Self::byte_budget(&base, counter, submit.clone());
+            if let Some(editable) = entry.dynamic_cast_ref::<gtk::Editable>() {
+                editable.connect_changed(move |e| update(&e.text()));
+            }
+        }
+        let viewer = self.clone();
+        let editable = entry.clone().dynamic_cast::<gtk::Editable>().unwrap();
+        let button = submit.clone();
+        let submit_input = move || {
+            if button.is_sensitive() {
+                let query = urlencoding::encode(&editable.text()).into_owned();
+                viewer.visit(&format!("{base}?{query}"));
+            }
+        };
+        let activate = submit_input.clone();
+        submit.connect_clicked(move |_| submit_input());
+        if let Some(e) = entry.downcast_ref::<gtk::Entry>() {
+            e.connect_activate(move |_| activate());
+        } else if let Some(e) = entry.downcast_ref::<gtk::PasswordEntry>() {
+            e.connect_activate(move |_| activate());
+        }
+        let anchor = buf.create_child_anchor(&mut iter);
+        self.insert_widget(&inputbox, &anchor);
+        entry
+    }
+
+    /// Inserts a multi line text editor which, when submitted, visits `url`
+    /// with the percent encoded text as the query. The number of bytes
+    /// remaining before the 1024 byte limit on gemini requests is displayed.
+    fn insert_multiline_input(&self, url: &str) -> gtk::TextView {
+        let (buf, mut iter) = self.get_iter();
+        let upload = UploadWidget::new();
+        upload.set_input_mode();
+        upload.set_hexpand(true);
+        upload.set_halign(gtk::Align::Fill);
+        upload.set_height_request(self.height() / 2);
+        let base = Self::input_base(url);
+        let update = Self::byte_budget(&base, upload.counter(), upload.submit_button());
+        let editor = upload.editor();
+        editor.buffer().connect_changed(move |b| {
+            let (start, end) = b.bounds();
+            update(&b.text(&start, &end, false));
+        });
         let viewer = self.clone();
-        label.connect_activate_link(move |_, link| {
-            viewer.visit(link);
-            gtk::Inhibit(true)
+        let widget = upload.clone();
+        upload.submit_button().connect_clicked(move |_| {
+            let query = urlencoding::encode(&widget.text()).into_owned();
+            viewer.visit(&format!("{base}?{query}"));
+        });
+        let viewer = self.clone();
+        upload.cancel_button().connect_clicked(move |_| {
+            viewer.go_previous();
         });
+        let anchor = buf.create_child_anchor(&mut iter);
+        self.insert_widget(&upload, &anchor);
+        editor
     }
 
-    fn insert_prompt_link(&self, link: &str, text: Option<String>) {
-        match self.uri().split_once(':') {
-            Some((s, _)) if s == "spartan" => {
-                let start = "<span color=\"#0000ff\"> 📤  </span>";
-                let label = self.insert_gmi_link_markup_label(start, link, text);
-                let viewer = self.clone();
-                label.connect_activate_link(move |_, link| {
-                    let url = if let Some(("spartan", _)) = link.split_once(':') {
-                        link.to_string()
-                    } else {
-                        let u = viewer.uri();
-                        let u = Url::parse(&u).unwrap();
-                        u.join(link).unwrap().to_string()
-                    };
-                    viewer.set_uri(&url);
-                    viewer.emit_by_name::<()>("request-upload", &[&url]);
-                    gtk::Inhibit(true)
-                });
-            }
-            _ => {
-                let text = if let Some(t) = text {
-                    Cow::from(format!("=: {link} {t}"))
-                } else {
-                    Cow::from(link)
-                };
-                self.insert_text_block(&text, TextSize::Paragraph);
-            }
+    /// Strips the query and fragment from `url`, giving the url which input
+    /// is submitted to
+    fn input_base(url: &str) -> String {
+        let mut base = url.to_string();
+        if let Some(idx) = base.find(['?', '#']) {
+            base.truncate(idx);
         }
+        base
     }
 
-    fn insert_gmi_link_markup_label(
-        &self,
-        start: &str,
-        link: &str,
-        text: Option<String>,
-    ) -> gtk::Label {
-        let (buf, mut iter) = self.get_iter();
-        let link = link.replace('&', "&amp;");
-        let anchor = buf.create_child_anchor(&mut iter);
-        let label = gtk::Label::builder()
-            .use_markup(true)
-            .label(format!(
-                "{}<span font=\"{}\"><a href=\"{}\">{}</a></span>",
-                start,
-                self.font_paragraph(),
-                &link,
-                match text {
-                    Some(t) => self.wrap_text(
-                        &t,
-                        FontDescription::from_string(&self.font_paragraph()).size()
-                    ),
-                    None => self.wrap_text(
-                        &link,
-                        FontDescription::from_string(&self.font_paragraph()).size()
-                    ),
-                },
-            ))
-            .tooltip_text(if link.len() < 80 {
-                link
+    /// Shows in `counter` how many bytes of the 1024 byte limit on gemini
+    /// requests remain once the text is percent encoded and appended to `base`
+    /// as the query, and makes `button` insensitive when the limit is exceeded.
+    /// The returned closure is called with the text whenever it changes.
+    fn byte_budget(base: &str, counter: gtk::Label, button: gtk::Button) -> impl Fn(&str) {
+        let len = base.len() + 1;
+        let update = move |text: &str| {
+            let used = len + urlencoding::encode(text).len();
+            let remaining = 1024 - i64::try_from(used).unwrap_or(i64::MAX);
+            counter.set_label(&format!("{remaining} bytes remaining"));
+            if remaining < 0 {
+                counter.add_css_class("error");
             } else {
-                format!("{}...", &link[..80])
-            })
-            .build();
-        label.set_cursor_from_name(Some("pointer"));
-        self.add_child_at_anchor(&label, &anchor);
-        iter = buf.end_iter();
-        buf.insert(&mut iter, "\n");
-        label
-    }
-
-    /// Renders a `GopherMap`
-    fn render_gopher(&self, content: &Content) {
-        self.clear();
-        let buf = self.buffer();
-        let mut iter;
-        for line in content.parse() {
-            iter = buf.end_iter();
-            match line {
-                gopher::parser::LineType::Text(text) => {
-                    buf.insert_markup(
-                        &mut iter,
-                        &format!(
-                            "<span font=\"{}\">{}</span>\n",
-                            &self.font_pre(),
-                            glib::markup_escape_text(&text)
-                        ),
-                    );
-                }
-                gopher::parser::LineType::Link(link) => {
-                    let label = link.to_label(&FontDescription::from_string(&self.font_pre()));
-                    self.insert_gopher_link(&label);
-                    label.set_extra_menu(Some(&Self::context_menu(&link.to_string())));
-                    let viewer = self.clone();
-                    label.connect_activate_link(move |_, link| {
-                        viewer.visit(link);
-                        gtk::Inhibit(true)
-                    });
-                }
-                gopher::parser::LineType::Query(link) => {
-                    let label = link.to_label(&FontDescription::from_string(&self.font_pre()));
-                    self.insert_gopher_link(&label);
-                    let viewer = self.clone();
-                    label.connect_activate_link(move |_, link| {
-                        viewer.emit_by_name::<()>(
-                            "request-input",
-                            &[&String::from("Enter query"), &link],
-                        );
-                        gtk::Inhibit(true)
-                    });
-                }
-                gopher::parser::LineType::Http(link) => {
-                    let label = link.to_label(&FontDescription::from_string(&self.font_pre()));
-                    self.insert_gopher_link(&label);
-                    label.set_extra_menu(Some(&Self::context_menu(&link.url)));
-                    let viewer = self.clone();
-                    label.connect_activate_link(move |_, link| {
-                        viewer.visit(link);
-                        gtk::Inhibit(true)
-                    });
-                }
+                counter.remove_css_class("error");
             }
-        }
+            button.set_sensitive(remaining >= 0);
+        };
+        update("");
+        update
     }
 
-    fn insert_gopher_link(&self, label: &gtk::Label) {
-        let (buf, mut iter) = self.get_iter();
-        let anchor = buf.create_child_anchor(&mut iter);
-        self.add_child_at_anchor(label, &anchor);
-        iter = buf.end_iter();
-        buf.insert(&mut iter, "\n");
+    fn insert_gopher_text(&self, text: &str) {
+        let (_, mut iter) = self.get_iter();
+        self.insert_scaled_markup(
+            &mut iter,
+            &format!(
+                "<span font=\"{}\">{}</span>\n",
+                &self.font_pre(),
+                glib::markup_escape_text(text)
+            ),
+            &self.theme_tag("preformatted"),
+        );
     }
 
-    fn context_menu(link: &str) -> Menu {
+    fn context_menu(link: &str, title: &str) -> Menu {
         let menu = Menu::new();
         let url = urlencoding::encode(link);
         let action_name = format!("viewer.request-new-tab('{}')", &url);
         let in_tab = MenuItem::new(Some("Open in new tab"), Some(&action_name));
         let action_name = format!("viewer.request-new-window('{}')", &url);
         let in_window = MenuItem::new(Some("Open in new window"), Some(&action_name));
+        let action_name = format!(
+            "viewer.add-bookmark(('{}','{}'))",
+            &url,
+            urlencoding::encode(title)
+        );
+        let bookmark = MenuItem::new(Some("Add bookmark"), Some(&action_name));
         menu.append_item(&in_tab);
         menu.append_item(&in_window);
+        menu.append_item(&bookmark);
         menu
     }
 
     /// Clears the text buffer
     pub fn clear(&self) {
+        let imp = self.imp();
+        imp.input_form.set(false);
+        *imp.feed.borrow_mut() = None;
+        *imp.animation.borrow_mut() = None;
+        imp.plus_links.borrow_mut().clear();
+        self.hide_link_hints();
+        self.unhover_link();
+        imp.links.borrow_mut().clear();
+        self.queue_links_changed();
         let buf = self.buffer();
         let (mut start, mut end) = buf.bounds();
         buf.delete(&mut start, &mut end);
@@ -552,7 +2141,9 @@ impl GemView {
     fn absolute_url(&self, url: &str) -> Result<Url, Box<dyn std::error::Error>> {
         match Url::parse(url) {
             Ok(u) => match u.scheme() {
-                "gemini" | "mercury" | "data" | "gopher" | "finger" | "file" | "spartan" => Ok(u),
+                "about" | "gemini" | "mercury" | "data" | "gopher" | "finger" | "file"
+                | "spartan" => Ok(u),
+                s if self.proxy(s).is_some() => Ok(u),
                 s => {
                     self.emit_by_name::<()>("request-unsupported-scheme", &[&url.to_string()]);
                     Err(format!("unsupported-scheme: {s}").into())
@@ -569,6 +2160,163 @@ impl GemView {
         }
     }
 
+    /// Sets a gemini proxy, given as a `gemini://host:port` url, through which
+    /// all requests for urls with the given scheme will be sent. This allows
+    /// schemes such as `https` to be browsed through a gateway rather than
+    /// being passed to the "request-unsupported-scheme" signal. Passing `None`
+    /// removes the proxy for that scheme. The "proxies" property is updated
+    /// to match.
+    pub fn set_proxy(&self, scheme: &str, proxy: Option<&str>) {
+        let mut proxies = self.imp().proxy_map.borrow().clone();
+        match proxy {
+            Some(p) => proxies.insert(scheme.to_string(), p.to_string()),
+            None => proxies.remove(scheme),
+        };
+        let mut pairs = proxies
+            .iter()
+            .map(|(scheme, proxy)| format!("{scheme}={proxy}"))
+            .collect::<Vec<_>>();
+        pairs.sort();
+        self.set_proxies(pairs.join(","));
+    }
+
+    #[must_use]
+    /// Returns the gemini proxy used for the given scheme, if any
+    pub fn proxy(&self, scheme: &str) -> Option<String> {
+        self.imp().proxy_map.borrow().get(scheme).cloned()
+    }
+
+    /// Reads the map of schemes to gemini proxies from the "proxies" property.
+    /// Pairs without an `=` are skipped.
+    fn parse_proxies(&self) {
+        let proxies = self
+            .proxies()
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(scheme, proxy)| (scheme.trim().to_lowercase(), proxy.trim().to_string()))
+            .filter(|(scheme, proxy)| !scheme.is_empty() && !proxy.is_empty())
+            .collect();
+        *self.imp().proxy_map.borrow_mut() = proxies;
+    }
+
+    /// Returns the "socks-proxy" property as an address, or `None` if it is
+    /// unset
+    fn socks_address(&self) -> Option<String> {
+        let proxy = self.socks_proxy();
+        if proxy.is_empty() {
+            None
+        } else {
+            Some(proxy)
+        }
+    }
+
+    #[must_use]
+    /// Gets the colors used to render pages. Unless a theme has been set with
+    /// [`GemView::set_theme`], this is the light or dark preset according to
+    /// the color scheme preferred by the desktop. If the "host-colors"
+    /// property is set, the accent colors are replaced by the palette of the
+    /// current host.
+    pub fn theme(&self) -> Theme {
+        let mut theme = self.base_theme();
+        let host = self.imp().current_host.borrow().clone();
+        if self.host_colors() && !host.is_empty() {
+            self.host_palette(&host).apply(&mut theme);
+        }
+        theme
+    }
+
+    fn base_theme(&self) -> Theme {
+        if let Some(ref theme) = *self.imp().theme.borrow() {
+            theme.clone()
+        } else if Self::prefers_dark() {
+            Theme::dark()
+        } else {
+            Theme::light()
+        }
+    }
+
+    #[must_use]
+    /// Gets the palette used for a host when the "host-colors" property is
+    /// set. This is the palette set with [`GemView::set_host_palette`], if
+    /// any, or else one derived from the hostname.
+    pub fn host_palette(&self, host: &str) -> Palette {
+        if let Some(palette) = self.imp().host_palettes.borrow().get(host) {
+            return palette.clone();
+        }
+        Palette::from_host(host, self.base_theme().is_dark())
+    }
+
+    /// Sets the palette used for a host, replacing the one derived from the
+    /// hostname. Passing `None` returns to the derived palette.
+    pub fn set_host_palette(&self, host: &str, palette: Option<Palette>) {
+        {
+            let mut palettes = self.imp().host_palettes.borrow_mut();
+            match palette {
+                Some(p) => palettes.insert(host.to_string(), p),
+                None => palettes.remove(host),
+            };
+        }
+        if *self.imp().current_host.borrow() == host {
+            self.apply_theme();
+        }
+    }
+
+    /// Records the host of a newly loaded page, updating the colors if the
+    /// "host-colors" property is set and the host has changed
+    fn update_host(&self, uri: &str) {
+        let host = Url::parse(uri)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let changed = *self.imp().current_host.borrow() != host;
+        if changed {
+            *self.imp().current_host.borrow_mut() = host;
+            if self.host_colors() {
+                self.apply_theme();
+            }
+        }
+    }
+
+    /// Sets the colors used to render pages. Passing `None` returns to
+    /// following the color scheme preferred by the desktop.
+    pub fn set_theme(&self, theme: Option<Theme>) {
+        *self.imp().theme.borrow_mut() = theme;
+        self.apply_theme();
+    }
+
+    /// Returns `true` if the "gtk-application-prefer-dark-theme" setting is
+    /// set. Dark Gtk themes which do not set it, such as one selected by name,
+    /// are not detected and get the light preset unless a theme is set with
+    /// [`GemView::set_theme`].
+    fn prefers_dark() -> bool {
+        gtk::Settings::default().map_or(false, |settings| {
+            settings.is_gtk_application_prefer_dark_theme()
+        })
+    }
+
+    /// Applies the current theme to the text tags and the page background
+    fn apply_theme(&self) {
+        let theme = self.theme();
+        self.paragraph_tag().set_foreground(Some(&theme.text));
+        for tag in [self.h1_tag(), self.h2_tag(), self.h3_tag()] {
+            tag.set_foreground(Some(&theme.heading));
+        }
+        self.theme_tag("quote").set_foreground(Some(&theme.quote));
+        self.theme_tag("preformatted")
+            .set_foreground(Some(&theme.preformatted));
+        self.imp().css.load_from_data(&theme.to_css());
+    }
+
+    /// Resolves a possibly relative url against the current page, without
+    /// checking whether the scheme is supported
+    fn join_url(&self, url: &str) -> Result<Url, url::ParseError> {
+        match Url::parse(url) {
+            Ok(u) => Ok(u),
+            Err(url::ParseError::RelativeUrlWithoutBase) => Url::parse(&self.uri())?.join(url),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Parse the given uri and then visits the page
     pub fn visit(&self, addr: &str) {
         self.load(addr);
@@ -584,9 +2332,15 @@ impl GemView {
                 return;
             }
         };
+        let proxy = self.proxy(url.scheme()).and_then(|p| Url::parse(&p).ok());
+        if let Some(proxy) = proxy {
+            self.load_gemini(url, Some(proxy));
+            return;
+        }
         match url.scheme() {
+            "about" => self.load_about(&url),
             "data" => self.load_data(&url),
-            "gemini" => self.load_gemini(url),
+            "gemini" => self.load_gemini(url, None),
             "gopher" => self.load_gopher(url),
             "file" => self.load_file(&url),
             "finger" => self.load_finger(url),
@@ -595,6 +2349,28 @@ impl GemView {
         }
     }
 
+    /// Loads one of the internal pages. Currently `about:blank`,
+    /// `about:bookmarks` and `about:feeds` are supported.
+    fn load_about(&self, url: &Url) {
+        let page = match url.path() {
+            "blank" => String::new(),
+            "bookmarks" => self.imp().bookmarks.borrow().to_gemtext(),
+            "feeds" => self.imp().subscriptions.borrow().to_gemtext(),
+            p => {
+                let estr = format!("unknown page: about:{p}");
+                self.emit_by_name::<()>("page-load-failed", &[&estr]);
+                return;
+            }
+        };
+        let url = url.to_string();
+        self.append_history(&url);
+        self.set_buffer_mime("text/gemini");
+        self.set_buffer_content(page.as_bytes());
+        self.render_gmi(&page);
+        *self.imp().feed.borrow_mut() = None;
+        self.emit_by_name::<()>("page-loaded", &[&url]);
+    }
+
     fn load_data(&self, url: &Url) {
         let data = match DataUrl::try_from(url.to_string().as_str()) {
             Ok(d) => d,
@@ -673,7 +2449,7 @@ impl GemView {
                     self.append_history(&url);
                     self.set_buffer_mime(&s);
                     self.set_buffer_content(&content.bytes);
-                    self.render_gmi(&String::from_utf8_lossy(&content.bytes));
+                    self.render_gmi(&self.decode_text(&content.bytes, None));
                     self.emit_by_name::<()>("page-loaded", &[&url]);
                 }
                 s if s.starts_with("text/") => {
@@ -681,7 +2457,7 @@ impl GemView {
                     self.append_history(&url);
                     self.set_buffer_mime(&s);
                     self.set_buffer_content(&content.bytes);
-                    self.render_text(&String::from_utf8_lossy(&content.bytes));
+                    self.render_text(&self.decode_text(&content.bytes, None));
                     self.emit_by_name::<()>("page-loaded", &[&url]);
                 }
                 s if s.starts_with("image/") => {
@@ -700,31 +2476,44 @@ impl GemView {
     fn load_gopher(&self, url: Url) {
         let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
         let req = url.clone();
-        thread::spawn(move || match gopher::request(&req) {
-            Ok(content) => {
-                sender
-                    .send(Response::Success(content))
-                    .expect("Cannot send data");
-            }
-            Err(e) => {
-                sender
-                    .send(Response::Error(format!("{e:?}")))
-                    .expect("Cannot send data");
-            }
+        let socks = self.socks_address();
+        thread::spawn(move || {
+            sender
+                .send(request::gopher(&req, socks.as_deref()))
+                .expect("Cannot send data");
         });
         let viewer = self.clone();
         receiver.attach(None, move |response| {
             match response {
-                Response::Success(content) => {
+                Response::Success(mut content) => {
+                    let kind = ItemType::from_url(url.as_str());
+                    if matches!(
+                        kind,
+                        ItemType::Sound | ItemType::Video | ItemType::Gif | ItemType::Image
+                    ) && !content.mime.starts_with("image")
+                    {
+                        content.mime = tree_magic_mini::from_u8(&content.bytes).to_string();
+                    }
                     viewer.set_buffer_mime(&content.mime);
                     viewer.set_buffer_content(&content.bytes);
-                    if content.mime.starts_with("text") {
+                    if kind.is_binary() {
+                        viewer.emit_by_name::<()>(
+                            "request-download",
+                            &[&content.mime, &Self::download_filename(&url)],
+                        );
+                    } else if content.mime.starts_with("text") {
                         let url = url.to_string();
                         viewer.append_history(&url);
-                        if content.is_map() {
-                            viewer.render_gopher(&content);
+                        let text = viewer.decode_text(&content.bytes, None);
+                        if content.is_map()
+                            || matches!(kind, ItemType::Directory | ItemType::Search)
+                        {
+                            viewer.render_gopher(&Content {
+                                bytes: text.into_bytes(),
+                                ..content
+                            });
                         } else {
-                            viewer.render_text(&String::from_utf8_lossy(&content.bytes));
+                            viewer.render_text(&text);
                         }
                         viewer.emit_by_name::<()>("page-loaded", &[&url]);
                     } else if content.mime.starts_with("image") {
@@ -732,15 +2521,16 @@ impl GemView {
                         viewer.append_history(&url);
                         viewer.render_image_from_bytes(&content.bytes);
                         viewer.emit_by_name::<()>("page-loaded", &[&url]);
+                    } else if viewer.is_playable(&content.mime) {
+                        let url = url.to_string();
+                        viewer.append_history(&url);
+                        viewer.render_media_from_bytes(&content.bytes, &content.mime);
+                        viewer.emit_by_name::<()>("page-loaded", &[&url]);
                     } else {
-                        let filename = if let Some(segments) = url.path_segments() {
-                            segments.last().unwrap_or("download")
-                        } else {
-                            "download"
-                        }
-                        .to_string();
-
-                        viewer.emit_by_name::<()>("request-download", &[&content.mime, &filename]);
+                        viewer.emit_by_name::<()>(
+                            "request-download",
+                            &[&content.mime, &Self::download_filename(&url)],
+                        );
                     }
                 }
                 Response::Error(err) => {
@@ -755,17 +2545,11 @@ impl GemView {
     fn load_finger(&self, url: Url) {
         let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
         let req = url.clone();
-        thread::spawn(move || match finger::request(&req) {
-            Ok(content) => {
-                sender
-                    .send(Response::Success(content))
-                    .expect("Cannot send data");
-            }
-            Err(e) => {
-                sender
-                    .send(Response::Error(format!("{e:?}")))
-                    .expect("Cannot send data");
-            }
+        let socks = self.socks_address();
+        thread::spawn(move || {
+            sender
+                .send(request::finger(&req, socks.as_deref()))
+                .expect("Cannot send data");
         });
         let viewer = self.clone();
         receiver.attach(None, move |response| {
@@ -775,7 +2559,7 @@ impl GemView {
                     viewer.append_history(&url);
                     viewer.set_buffer_mime(&content.mime);
                     viewer.set_buffer_content(&content.bytes);
-                    viewer.render_text(&String::from_utf8_lossy(&content.bytes));
+                    viewer.render_text(&viewer.decode_text(&content.bytes, None));
                     viewer.emit_by_name::<()>("page-loaded", &[&url]);
                 }
                 Response::Error(err) => {
@@ -790,26 +2574,11 @@ impl GemView {
     fn load_spartan(&self, url: Url) {
         let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
         let u = url.clone();
+        let socks = self.socks_address();
         thread::spawn(move || {
-            let mut url = u;
-            loop {
-                let response = match spartan::request(&url) {
-                    Ok(r) => r,
-                    Err(e) => {
-                        let estr = format!("{e:?}");
-                        sender
-                            .send(Response::Error(estr))
-                            .expect("Cannot send data");
-                        break;
-                    }
-                };
-                let msg = response.into_message(&mut url);
-                if let Response::Redirect(_) = msg {
-                    continue;
-                };
-                sender.send(msg).expect("Cannot send message");
-                break;
-            }
+            sender
+                .send(request::spartan(u, socks.as_deref()))
+                .expect("Cannot send data");
         });
         let viewer = self.clone();
         receiver.attach(None, move |response| {
@@ -829,26 +2598,11 @@ impl GemView {
     pub fn post_spartan(&self, url: Url, data: Vec<u8>) {
         let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
         let u = url.clone();
+        let socks = self.socks_address();
         thread::spawn(move || {
-            let mut url = u;
-            loop {
-                let response = match spartan::post(&url, &data) {
-                    Ok(r) => r,
-                    Err(e) => {
-                        let estr = format!("{e:?}");
-                        sender
-                            .send(Response::Error(estr))
-                            .expect("Cannot send data");
-                        break;
-                    }
-                };
-                let msg = response.into_message(&mut url);
-                if let Response::Redirect(_) = msg {
-                    continue;
-                };
-                sender.send(msg).expect("Cannot send message");
-                break;
-            }
+            sender
+                .send(request::spartan_post(u, data, socks.as_deref()))
+                .expect("Cannot send data");
         });
         let viewer = self.clone();
         receiver.attach(None, move |response| {
@@ -866,87 +2620,24 @@ impl GemView {
         });
     }
 
-    fn load_gemini(&self, url: Url) {
+    /// Loads a gemini url, or a url of any scheme if a gemini proxy is given
+    fn load_gemini(&self, url: Url, proxy: Option<Url>) {
         let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
         let u = url.clone();
+        let socks = self.socks_address();
         thread::spawn(move || {
-            let mut url = u;
-            loop {
-                let response = match gemini::request::request(&url) {
-                    Ok(r) => r,
-                    Err(e) => {
-                        let estr = format!("{e:?}");
-                        sender
-                            .send(Response::Error(estr))
-                            .expect("Cannot send data");
-                        break;
-                    }
-                };
-                match response.status {
-                    gemini::protocol::StatusCode::Redirect(c) => {
-                        println!("Redirect code {c} with meta {}", response.meta);
-                        url = match Url::try_from(response.meta.as_str()) {
-                            Ok(r) => r,
-                            Err(e) => {
-                                let estr = format!("{e:?}");
-                                sender
-                                    .send(Response::Error(estr))
-                                    .expect("Cannot send data");
-                                break;
-                            }
-                        };
-                    }
-                    gemini::protocol::StatusCode::Success(_) => {
-                        let mime = if response.meta.starts_with("text/gemini") {
-                            String::from("text/gemini")
-                        } else if let Some((mime, _)) = response.meta.split_once(';') {
-                            String::from(mime)
-                        } else {
-                            response.meta
-                        };
-                        let url = Some(url.to_string());
-                        let content = Content {
-                            url,
-                            mime,
-                            bytes: response.data,
-                        };
-                        sender
-                            .send(Response::Success(content))
-                            .expect("Cannot send data");
-                        break;
-                    }
-                    gemini::protocol::StatusCode::Input(sensitive) => {
-                        let input = Input {
-                            meta: response.meta,
-                            url: url.to_string(),
-                            sensitive,
-                        };
-                        sender
-                            .send(Response::RequestInput(input))
-                            .expect("Cannot send data");
-                        break;
-                    }
-                    s => {
-                        let estr = format!("{s:?}");
-                        sender
-                            .send(Response::Error(estr))
-                            .expect("Cannot send data");
-                        break;
-                    }
-                }
-            }
+            let response = match proxy {
+                Some(proxy) => request::gemini_proxy(u, &proxy, socks.as_deref()),
+                None => request::gemini(u, socks.as_deref()),
+            };
+            sender.send(response).expect("Cannot send data");
         });
         let viewer = self.clone();
         receiver.attach(None, move |response| {
             match response {
                 Response::RequestInput(input) => {
-                    let signal = if input.sensitive == 1 {
-                        "request-input-sensitive"
-                    } else {
-                        "request-input"
-                    };
                     viewer.append_history(&input.url);
-                    viewer.emit_by_name::<()>(signal, &[&input.meta, &input.url]);
+                    viewer.request_input(&input.meta, &input.url, input.sensitive == 1);
                 }
                 Response::Success(content) => {
                     viewer.process_gemini_response_success(&content, &url);
@@ -961,18 +2652,29 @@ impl GemView {
     }
 
     fn process_gemini_response_success(&self, content: &Content, url: &Url) {
-        self.set_buffer_mime(&content.mime);
+        let (mime, charset) = charset::split_mime(&content.mime);
+        self.set_buffer_mime(mime);
         self.set_buffer_content(&content.bytes);
+        self.set_page_language(charset::mime_param(&content.mime, "lang").unwrap_or_default());
         let end_url = content.url.as_ref().unwrap();
-        match content.mime.as_str() {
+        match mime {
             "text/gemini" => {
                 self.append_history(end_url);
-                self.render_gmi(&String::from_utf8_lossy(&content.bytes));
+                self.render_gmi(&self.decode_text(&content.bytes, charset));
+                self.emit_by_name::<()>("page-loaded", &[end_url]);
+            }
+            s if feed::is_xml_mime(s) => {
+                self.append_history(end_url);
+                let text = self.decode_text(&content.bytes, charset);
+                match Feed::from_xml(&text, end_url) {
+                    Some(feed) => self.render_feed(feed),
+                    None => self.render_text(&text),
+                }
                 self.emit_by_name::<()>("page-loaded", &[end_url]);
             }
             s if s.starts_with("text/") => {
                 self.append_history(end_url);
-                self.render_text(&String::from_utf8_lossy(&content.bytes));
+                self.render_text(&self.decode_text(&content.bytes, charset));
                 self.emit_by_name::<()>("page-loaded", &[end_url]);
             }
             s if s.starts_with("image") => {
@@ -980,27 +2682,115 @@ impl GemView {
                 self.render_image_from_bytes(&content.bytes);
                 self.emit_by_name::<()>("page-loaded", &[end_url]);
             }
+            s if self.is_playable(s) => {
+                self.append_history(end_url);
+                self.render_media_from_bytes(&content.bytes, s);
+                self.emit_by_name::<()>("page-loaded", &[end_url]);
+            }
             _ => {
                 let derived = tree_magic_mini::from_u8(&content.bytes);
                 if derived.starts_with("text") {
                     self.append_history(end_url);
-                    self.render_text(&String::from_utf8_lossy(&content.bytes));
+                    self.render_text(&self.decode_text(&content.bytes, charset));
                     self.emit_by_name::<()>("page-loaded", &[end_url]);
                 } else if derived.starts_with("image") {
                     self.append_history(end_url);
                     self.render_image_from_bytes(&content.bytes);
                     self.emit_by_name::<()>("page-loaded", &[end_url]);
+                } else if self.is_playable(derived) {
+                    self.append_history(end_url);
+                    self.render_media_from_bytes(&content.bytes, derived);
+                    self.emit_by_name::<()>("page-loaded", &[end_url]);
                 } else {
-                    let filename = if let Some(segments) = url.path_segments() {
-                        segments.last().unwrap_or("download")
-                    } else {
-                        "download"
-                    }
-                    .to_string();
-                    self.emit_by_name::<()>("request-download", &[&content.mime, &filename]);
+                    self.emit_by_name::<()>(
+                        "request-download",
+                        &[&mime, &Self::download_filename(url)],
+                    );
+                }
+            }
+        }
+    }
+
+    /// Decodes the text of the current page and records the encoding used in
+    /// the "page-encoding" property. The "encoding-override" property, if set,
+    /// takes precedence over the charset given by the server.
+    fn decode_text(&self, bytes: &[u8], charset: Option<&str>) -> String {
+        let forced = self.encoding_override();
+        let (text, encoding) = charset::decode(bytes, charset::label(&forced, charset));
+        *self.imp().page_charset.borrow_mut() = charset.map(str::to_string);
+        *self.imp().page_encoding.borrow_mut() = encoding.to_string();
+        self.notify("page-encoding");
+        text
+    }
+
+    /// Renders the current page again from its source in the buffer, without
+    /// touching the network. This is done automatically when the fonts or
+    /// "encoding-override" change, so that every element of the page is
+    /// restyled and text is decoded again. Theme colors are applied in place
+    /// and need no new render. The scroll position is kept.
+    pub fn re_render(&self) {
+        let mime = self.buffer_mime();
+        if mime.is_empty() || self.imp().input_form.get() {
+            return;
+        }
+        let uri = self.uri();
+        let bytes = self.buffer_content();
+        let charset = self.imp().page_charset.borrow().clone();
+        let charset = charset.as_deref();
+        let scroll = self.vadjustment().map(|a| a.value());
+        match mime.as_str() {
+            "text/gemini" => {
+                self.render_gmi(&self.decode_text(&bytes, charset));
+                if uri.starts_with("about:") {
+                    *self.imp().feed.borrow_mut() = None;
+                }
+            }
+            s if feed::is_xml_mime(s) => {
+                let text = self.decode_text(&bytes, charset);
+                match Feed::from_xml(&text, &uri) {
+                    Some(feed) => self.render_feed(feed),
+                    None => self.render_text(&text),
                 }
             }
+            s if s.starts_with("text/") || s == "application/gopher-menu" => {
+                let text = self.decode_text(&bytes, charset);
+                let content = Content {
+                    url: Some(uri.clone()),
+                    mime: mime.clone(),
+                    bytes: text.into_bytes(),
+                };
+                let is_map = uri.starts_with("gopher:")
+                    && (content.is_map()
+                        || s == "application/gopher-menu"
+                        || matches!(
+                            ItemType::from_url(&uri),
+                            ItemType::Directory | ItemType::Search
+                        ));
+                if is_map {
+                    self.render_gopher(&content);
+                } else {
+                    self.render_text(&String::from_utf8_lossy(&content.bytes));
+                }
+            }
+            // Images and media have nothing to restyle, and rebuilding them
+            // would restart playback
+            _ => {}
+        }
+        if let (Some(adjustment), Some(value)) = (self.vadjustment(), scroll) {
+            // The new content has not been measured yet, so the scroll position
+            // is restored once the page has been laid out
+            glib::idle_add_local_once(move || adjustment.set_value(value));
+        }
+    }
+
+    /// Gets a filename for a download from the last segment of the url path
+    fn download_filename(url: &Url) -> String {
+        if let Some(segments) = url.path_segments() {
+            segments.last().unwrap_or("download")
+        } else {
+            "download"
         }
+        .to_string()
     }
 
     /// Reloads the current page
@@ -1098,7 +2888,9 @@ impl GemView {
     }
 
     /// Connects to the "request-new-tab" signal, emitted when the "Open in new
-    /// tab" item is chosen from the context menu for link items.
+    /// tab" item is chosen from the context menu for link items, or a link is
+    /// middle clicked or Ctrl+clicked. The signal handler receives the
+    /// absolute url of the link.
     pub fn connect_request_new_tab<F: Fn(&Self, String) + 'static>(
         &self,
         f: F,
@@ -1112,7 +2904,9 @@ impl GemView {
     }
 
     /// Connects to the "request-new-window" signal, emitted when the "Open in
-    /// new window" item is chosen from the context menu for link items.
+    /// new window" item is chosen from the context menu for link items, or a
+    /// link is Shift+clicked. The signal handler receives the absolute url of
+    /// the link.
     pub fn connect_request_new_window<F: Fn(&Self, String) + 'static>(
         &self,
         f: F,
@@ -1171,15 +2965,123 @@ impl GemView {
         })
     }
 
-    fn wrap_text(&self, text: &str, font_size: i32) -> String {
-        let mut factor = font_size / 1525;
-        if factor == 0 {
-            factor = 1;
-        }
-        let width: usize = match self.root() {
-            Some(win) => std::cmp::min((win.width() / factor).try_into().unwrap(), 175),
-            None => 175,
-        };
-        fill(glib::markup_escape_text(text).as_str(), width)
+    /// Connects to the "bookmarks-changed" signal, emitted whenever a bookmark
+    /// is added or removed, including from the link context menu
+    pub fn connect_bookmarks_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("bookmarks-changed", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            f(&obj);
+            None
+        })
+    }
+
+    /// Connects to the "bookmarks-file-error" signal, emitted when the file
+    /// named by the "bookmarks-file" property cannot be read or written. The
+    /// signal handler receives the error message. Once the file has failed to
+    /// load, changes are kept in memory only until it loads successfully.
+    pub fn connect_bookmarks_file_error<F: Fn(&Self, String) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_local("bookmarks-file-error", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            let err = values[1].get::<String>().unwrap();
+            f(&obj, err);
+            None
+        })
+    }
+
+    /// Connects to the "feeds-updated" signal, emitted after the subscribed
+    /// feeds have been checked for updates. The signal handler receives the
+    /// number of new entries.
+    pub fn connect_feeds_updated<F: Fn(&Self, u32) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_local("feeds-updated", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            let new = values[1].get::<u32>().unwrap();
+            f(&obj, new);
+            None
+        })
+    }
+
+    /// Connects to the "feeds-file-error" signal, emitted when the file named
+    /// by the "feeds-file" property cannot be read or written. The signal
+    /// handler receives the error message. Once the file has failed to load,
+    /// changes are kept in memory only until it loads successfully.
+    pub fn connect_feeds_file_error<F: Fn(&Self, String) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_local("feeds-file-error", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            let err = values[1].get::<String>().unwrap();
+            f(&obj, err);
+            None
+        })
+    }
+
+    /// Connects to the "link-hovered" signal, emitted when the pointer moves
+    /// over a link. The signal handler receives the full url of the link,
+    /// which may be shown in a status bar.
+    pub fn connect_link_hovered<F: Fn(&Self, String) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_local("link-hovered", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            let url = values[1].get::<String>().unwrap();
+            f(&obj, url);
+            None
+        })
+    }
+
+    /// Connects to the "link-unhovered" signal, emitted when the pointer
+    /// leaves a link
+    pub fn connect_link_unhovered<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("link-unhovered", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            f(&obj);
+            None
+        })
+    }
+
+    /// Connects to the "decide-navigation" signal, emitted before a link is
+    /// followed, whether by a click, the keyboard, [`GemView::follow_link`]
+    /// or the link's context menu, and before a link is opened in a new tab
+    /// or window. The signal handler receives the url of the
+    /// link resolved against the current page, what caused it to be followed
+    /// and the modifier keys and mouse buttons held at the time. It can allow
+    /// the link to be followed, deny it, or redirect it to another url. The
+    /// first handler to return a decision is used.
+    pub fn connect_decide_navigation<
+        F: Fn(&Self, &str, NavigationTrigger, ModifierType) -> NavigationDecision + 'static,
+    >(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_local("decide-navigation", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            let url = values[1].get::<String>().unwrap();
+            let trigger = values[2].get::<NavigationTrigger>().unwrap();
+            let modifiers = values[3].get::<ModifierType>().unwrap();
+            let decision = match f(&obj, &url, trigger, modifiers) {
+                NavigationDecision::Allow => url,
+                NavigationDecision::Deny => String::new(),
+                NavigationDecision::Redirect(u) => u,
+            };
+            Some(decision.to_value())
+        })
+    }
+
+    /// Connects to the "links-changed" signal, emitted once a page has been
+    /// rendered so that the list returned by [`GemView::links`] can be read
+    pub fn connect_links_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_local("links-changed", true, move |values| {
+            let obj = values[0].get::<Self>().unwrap();
+            f(&obj);
+            None
+        })
     }
 }
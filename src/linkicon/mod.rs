@@ -0,0 +1,60 @@
+use {
+    crate::itemtype::{self, ItemType},
+    url::Url,
+};
+
+/// The decoration shown in front of a link
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkIcon {
+    /// Text, usually an emoji, drawn in the theme's icon color for the scheme
+    Text(String),
+    /// The name of an icon in the icon theme, eg "network-server-symbolic"
+    Named(String),
+    /// No icon at all
+    Hidden,
+}
+
+/// Gets the key used to look up the icon for a link. This is the scheme of
+/// the url, taken from `base` for relative links. Gopher links also include
+/// their item type, eg "gopher:1" for a menu or "gopher:0" for a text file.
+pub(crate) fn icon_key(url: &str, base: &str) -> String {
+    let absolute = Url::parse(url).or_else(|_| Url::parse(base).and_then(|b| b.join(url)));
+    match absolute {
+        Ok(u) => match itemtype::type_char(&u) {
+            Some(kind) => format!("gopher:{kind}"),
+            None => u.scheme().to_string(),
+        },
+        Err(_) => String::from("gemini"),
+    }
+}
+
+/// Gets the scheme whose theme color is used for an icon key
+pub(crate) fn icon_scheme(key: &str) -> &str {
+    match key {
+        "upload" => "spartan",
+        k => k.split(':').next().unwrap_or(k),
+    }
+}
+
+/// The icon used for a key when none has been set by the application
+pub(crate) fn default_icon(key: &str) -> LinkIcon {
+    let icon = match key {
+        "gemini" => "🛰️",
+        "spartan" => "🗡️",
+        "upload" => "📤",
+        "finger" => "👉",
+        "data" => "📊",
+        "mailto" => "✉️",
+        "file" => "🗄️",
+        "gopher" => "🕳️",
+        k => match k.strip_prefix("gopher:").and_then(|t| t.chars().next()) {
+            Some(t) => ItemType::from(t).icon(),
+            None => "🌐",
+        },
+    };
+    if icon.is_empty() {
+        LinkIcon::Hidden
+    } else {
+        LinkIcon::Text(icon.to_string())
+    }
+}
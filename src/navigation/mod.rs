@@ -0,0 +1,36 @@
+use gtk::glib;
+
+/// What caused a link to be followed, as passed to the "decide-navigation"
+/// signal
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "GemViewNavigationTrigger")]
+pub enum NavigationTrigger {
+    /// A link was clicked
+    #[default]
+    Click,
+    /// A focused link was activated with the keyboard, or chosen with a link
+    /// hint
+    Keyboard,
+    /// A link was followed by calling [`crate::GemView::follow_link`]
+    Api,
+    /// A link is to be opened in a new tab, from its context menu or by a
+    /// middle click or Ctrl+click
+    NewTab,
+    /// A link is to be opened in a new window, from its context menu or by a
+    /// Shift+click
+    NewWindow,
+    /// Another entry in a link's context menu was chosen, such as an
+    /// alternate view of a Gopher+ item
+    ContextMenu,
+}
+
+/// The answer given by a "decide-navigation" signal handler
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NavigationDecision {
+    /// Follow the link
+    Allow,
+    /// Do nothing
+    Deny,
+    /// Follow the link to the given url instead
+    Redirect(String),
+}
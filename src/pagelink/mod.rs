@@ -0,0 +1,81 @@
+use {crate::itemtype, url::Url};
+
+/// A link on the current page, as listed by [`crate::GemView::links`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageLink {
+    /// The position of the link among the links of the page, counting from 0
+    pub index: usize,
+    /// The url the link points to, resolved against the url of the page
+    pub url: String,
+    /// The text displayed for the link
+    pub label: String,
+    /// The kind of line the link came from
+    pub kind: LinkKind,
+    /// The line of the page the link is on, counting from 0
+    pub line: i32,
+}
+
+/// The kinds of line which are rendered as links
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkKind {
+    /// A gemtext link line
+    Link,
+    /// A spartan prompt line, which uploads input to its url
+    Prompt,
+    /// An item in a gopher menu, with the character giving its item type
+    Gopher(char),
+}
+
+impl LinkKind {
+    /// Gets the kind of a gopher menu item from the item type of its url
+    pub(crate) fn from_gopher_url(url: &str) -> Self {
+        let item = Url::parse(url)
+            .ok()
+            .and_then(|u| itemtype::type_char(&u))
+            .unwrap_or('1');
+        Self::Gopher(item)
+    }
+
+    /// Returns `true` if following the link loads a page, which can also be
+    /// opened in a new tab or window. Prompts, telnet sessions, CSO lookups
+    /// and gopher searches ask for input or leave the browser instead.
+    pub(crate) fn opens_page(self) -> bool {
+        !matches!(self, Self::Prompt | Self::Gopher('2' | '7' | '8' | 'T'))
+    }
+}
+
+/// A link which has been rendered, along with the widgets needed to focus,
+/// scroll to and follow it from the keyboard
+pub(crate) struct RenderedLink {
+    pub link: PageLink,
+    /// The url as it appears in the link markup, which is passed to the
+    /// label's "activate-link" handler when the link is followed
+    pub href: String,
+    pub label: gtk::Label,
+    pub anchor: gtk::TextChildAnchor,
+}
+
+/// A hint overlaid on a link while choosing one from the keyboard
+pub(crate) struct LinkHint {
+    pub keys: String,
+    pub index: usize,
+    pub widget: gtk::Widget,
+}
+
+/// The keys used for link hints, starting with the home row
+const HINT_KEYS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// Generates `count` distinct hints. A single key is used for each link when
+/// there are few enough of them, otherwise every hint is two keys long so
+/// that no hint is the start of another.
+pub(crate) fn hint_keys(count: usize) -> Vec<String> {
+    let keys = HINT_KEYS.chars().collect::<Vec<_>>();
+    if count <= keys.len() {
+        keys.iter().take(count).map(char::to_string).collect()
+    } else {
+        keys.iter()
+            .flat_map(|a| keys.iter().map(move |b| format!("{a}{b}")))
+            .take(count)
+            .collect()
+    }
+}
@@ -0,0 +1,551 @@
+use {
+    crate::{
+        charset,
+        gopherplus::{self, Location},
+        itemtype::ItemType,
+    },
+    bucky::{
+        finger, gemini, gopher,
+        prelude::{Content, Input, Response},
+        spartan,
+    },
+    socks::Socks5Stream,
+    std::{
+        fmt::Write as _,
+        io::{Read, Write},
+        net::TcpStream,
+        time::Duration,
+    },
+    url::Url,
+};
+
+/// Requests a gemini url, following redirects. If a SOCKS5 proxy is given
+/// the connection is made through it.
+pub(crate) fn gemini(mut url: Url, socks: Option<&str>) -> Response {
+    if socks.is_some() {
+        return gemini_via(url, None, socks);
+    }
+    loop {
+        let response = match gemini::request::request(&url) {
+            Ok(r) => r,
+            Err(e) => return Response::Error(format!("{e:?}")),
+        };
+        match response.status {
+            gemini::protocol::StatusCode::Redirect(c) => {
+                println!("Redirect code {c} with meta {}", response.meta);
+                url = match Url::try_from(response.meta.as_str()) {
+                    Ok(r) => r,
+                    Err(e) => return Response::Error(format!("{e:?}")),
+                };
+            }
+            gemini::protocol::StatusCode::Success(_) => {
+                let url = Some(url.to_string());
+                return Response::Success(Content {
+                    url,
+                    mime: gemini_mime(&response.meta),
+                    bytes: response.data,
+                });
+            }
+            gemini::protocol::StatusCode::Input(sensitive) => {
+                return Response::RequestInput(Input {
+                    meta: response.meta,
+                    url: url.to_string(),
+                    sensitive,
+                });
+            }
+            s => return Response::Error(format!("{s:?}")),
+        }
+    }
+}
+
+/// Strips the parameters from the meta field of a successful gemini response,
+/// leaving the bare mime type along with the charset and language, if they
+/// were given
+fn gemini_mime(meta: &str) -> String {
+    let (mime, charset) = charset::split_mime(meta);
+    let mut mime = mime.to_string();
+    if let Some(charset) = charset {
+        mime.push_str("; charset=");
+        mime.push_str(charset);
+    }
+    if let Some(lang) = charset::mime_param(meta, "lang") {
+        mime.push_str("; lang=");
+        mime.push_str(lang);
+    }
+    mime
+}
+
+/// Requests a url of any scheme through a gemini proxy. The full url is sent
+/// to the proxy server rather than to the host named in the url. Redirects
+/// are followed through the same proxy.
+pub(crate) fn gemini_proxy(url: Url, proxy: &Url, socks: Option<&str>) -> Response {
+    gemini_via(url, Some(proxy), socks)
+}
+
+/// Performs a gemini request over our own connection, either to the host
+/// named in the url or to a gemini proxy, following redirects
+fn gemini_via(mut url: Url, proxy: Option<&Url>, socks: Option<&str>) -> Response {
+    for _ in 0..5 {
+        let (header, data) = match gemini_request(&url, proxy.unwrap_or(&url), socks) {
+            Ok(r) => r,
+            Err(e) => return Response::Error(e),
+        };
+        let (status, meta) = header.split_once(' ').unwrap_or((&header, ""));
+        let meta = meta.trim();
+        match status.as_bytes() {
+            [b'1', s] => {
+                return Response::RequestInput(Input {
+                    meta: meta.to_string(),
+                    url: url.to_string(),
+                    sensitive: s.saturating_sub(b'0'),
+                });
+            }
+            [b'2', _] => {
+                return Response::Success(Content {
+                    url: Some(url.to_string()),
+                    mime: gemini_mime(meta),
+                    bytes: data,
+                });
+            }
+            [b'3', _] => {
+                url = match url.join(meta) {
+                    Ok(r) => r,
+                    Err(e) => return Response::Error(format!("{e:?}")),
+                };
+                if proxy.is_none() && url.scheme() != "gemini" {
+                    return Response::Error(format!("Redirect to unsupported url: {url}"));
+                }
+            }
+            _ => return Response::Error(format!("{status} {meta}")),
+        }
+    }
+    Response::Error(String::from("Too many redirects"))
+}
+
+/// Sends a single gemini request for `url` to `server`, returning the
+/// response header and body
+fn gemini_request(
+    url: &Url,
+    server: &Url,
+    socks: Option<&str>,
+) -> Result<(String, Vec<u8>), String> {
+    // IPv6 hosts are bracketed, which neither the socket nor the TLS
+    // connector accept
+    let host = server
+        .host_str()
+        .ok_or_else(|| format!("Invalid url: {server}"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let stream = connect(host, server.port().unwrap_or(1965), socks)?;
+    let connector = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .map_err(|e| format!("{e:?}"))?;
+    let mut stream = connector
+        .connect(host, stream)
+        .map_err(|e| format!("{e:?}"))?;
+    write!(stream, "{url}\r\n").map_err(|e| format!("{e:?}"))?;
+    let mut data = vec![];
+    // Many servers close the connection without a TLS close_notify, so a read
+    // error after data has been received is not treated as a failure
+    if let Err(e) = stream.read_to_end(&mut data) {
+        if data.is_empty() {
+            return Err(format!("{e:?}"));
+        }
+    }
+    split_header(&data)
+}
+
+/// Splits a response into its first line and the remaining data
+fn split_header(data: &[u8]) -> Result<(String, Vec<u8>), String> {
+    let end = data
+        .windows(2)
+        .position(|w| w == b"\r\n")
+        .ok_or_else(|| String::from("Invalid response header"))?;
+    let header = String::from_utf8_lossy(&data[..end]).to_string();
+    Ok((header, data[end + 2..].to_vec()))
+}
+
+/// Opens a connection to `host`, through a SOCKS5 proxy if one is given. When
+/// proxied, the hostname is resolved by the proxy rather than locally, so
+/// that Tor `.onion` addresses can be reached.
+fn connect(host: &str, port: u16, socks: Option<&str>) -> Result<TcpStream, String> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let stream = match socks {
+        Some(proxy) => Socks5Stream::connect(proxy, (host, port)).map(Socks5Stream::into_inner),
+        None => TcpStream::connect((host, port)),
+    }
+    .map_err(|e| format!("{e:?}"))?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(30)))
+        .map_err(|e| format!("{e:?}"))?;
+    Ok(stream)
+}
+
+/// Writes `request` to a new connection and reads the full response
+fn exchange(host: &str, port: u16, socks: Option<&str>, request: &[u8]) -> Result<Vec<u8>, String> {
+    let mut stream = connect(host, port, socks)?;
+    stream.write_all(request).map_err(|e| format!("{e:?}"))?;
+    let mut data = vec![];
+    stream
+        .read_to_end(&mut data)
+        .map_err(|e| format!("{e:?}"))?;
+    Ok(data)
+}
+
+/// Requests a spartan url, following redirects
+pub(crate) fn spartan(mut url: Url, socks: Option<&str>) -> Response {
+    if socks.is_some() {
+        let data = url
+            .query()
+            .map(|q| urlencoding::decode(q).map_or(q.to_string(), |q| q.to_string()))
+            .unwrap_or_default();
+        url.set_query(None);
+        return spartan_via(url, data.as_bytes(), socks);
+    }
+    loop {
+        let response = match spartan::request(&url) {
+            Ok(r) => r,
+            Err(e) => return Response::Error(format!("{e:?}")),
+        };
+        let msg = response.into_message(&mut url);
+        if let Response::Redirect(_) = msg {
+            continue;
+        };
+        return msg;
+    }
+}
+
+/// Uploads `data` to a spartan url, following redirects
+pub(crate) fn spartan_post(mut url: Url, data: Vec<u8>, socks: Option<&str>) -> Response {
+    if socks.is_some() {
+        return spartan_via(url, &data, socks);
+    }
+    loop {
+        let response = match spartan::post(&url, &data) {
+            Ok(r) => r,
+            Err(e) => return Response::Error(format!("{e:?}")),
+        };
+        let msg = response.into_message(&mut url);
+        if let Response::Redirect(_) = msg {
+            continue;
+        };
+        return msg;
+    }
+}
+
+/// Performs a spartan request over our own connection, following redirects
+fn spartan_via(mut url: Url, data: &[u8], socks: Option<&str>) -> Response {
+    for _ in 0..5 {
+        let Some(host) = url.host_str() else {
+            return Response::Error(format!("Invalid url: {url}"));
+        };
+        let path = if url.path().is_empty() {
+            "/"
+        } else {
+            url.path()
+        };
+        let mut request = format!("{host} {path} {}\r\n", data.len()).into_bytes();
+        request.extend_from_slice(data);
+        let response = exchange(host, url.port().unwrap_or(300), socks, &request)
+            .and_then(|r| split_header(&r));
+        let (header, bytes) = match response {
+            Ok(r) => r,
+            Err(e) => return Response::Error(e),
+        };
+        let (status, meta) = header.split_once(' ').unwrap_or((&header, ""));
+        let meta = meta.trim();
+        match status {
+            "2" => {
+                return Response::Success(Content {
+                    url: Some(url.to_string()),
+                    mime: gemini_mime(meta),
+                    bytes,
+                });
+            }
+            "3" => {
+                url = match url.join(meta) {
+                    Ok(r) => r,
+                    Err(e) => return Response::Error(format!("{e:?}")),
+                };
+            }
+            _ => return Response::Error(format!("{status} {meta}")),
+        }
+    }
+    Response::Error(String::from("Too many redirects"))
+}
+
+/// Requests a gopher url. CSO items are looked up with the query of the url.
+pub(crate) fn gopher(url: &Url, socks: Option<&str>) -> Response {
+    if ItemType::from_url(url.as_str()) == ItemType::Cso {
+        return cso(url, socks);
+    }
+    if socks.is_some() {
+        return gopher_via(url, socks);
+    }
+    match gopher::request(url) {
+        Ok(content) => Response::Success(content),
+        Err(e) => Response::Error(format!("{e:?}")),
+    }
+}
+
+/// Performs a gopher request over our own connection. The query of the url,
+/// if any, is sent as the search terms. The mime type is taken from the item
+/// type, falling back to sniffing the data.
+fn gopher_via(url: &Url, socks: Option<&str>) -> Response {
+    let Some(location) = Location::from_url(url.as_str()) else {
+        return Response::Error(format!("Invalid gopher url: {url}"));
+    };
+    let request = match url.query() {
+        Some(q) => {
+            let q = urlencoding::decode(q).map_or(q.to_string(), |q| q.to_string());
+            format!("{}\t{q}\r\n", location.selector)
+        }
+        None => format!("{}\r\n", location.selector),
+    };
+    let bytes = match exchange(&location.host, location.port, socks, request.as_bytes()) {
+        Ok(b) => b,
+        Err(e) => return Response::Error(e),
+    };
+    let mime = match ItemType::from_url(url.as_str()) {
+        ItemType::Text | ItemType::Directory | ItemType::Search | ItemType::Cso => {
+            String::from("text/plain")
+        }
+        _ => tree_magic_mini::from_u8(&bytes).to_string(),
+    };
+    Response::Success(Content {
+        url: Some(url.to_string()),
+        mime,
+        bytes,
+    })
+}
+
+/// Looks up the query of a CSO item's url on its phone book server. Unlike
+/// other gopher items the server speaks the ph protocol, which takes a
+/// `query` command and answers with numbered response lines.
+fn cso(url: &Url, socks: Option<&str>) -> Response {
+    let Some(location) = Location::from_url(url.as_str()) else {
+        return Response::Error(format!("Invalid gopher url: {url}"));
+    };
+    let Some(query) = url.query() else {
+        return Response::Error(String::from("A CSO lookup needs a query"));
+    };
+    let query = urlencoding::decode(query).map_or(query.to_string(), |q| q.to_string());
+    let query = query.replace(['\r', '\n'], " ");
+    let request = format!("query {}\r\nquit\r\n", query.trim());
+    match exchange(&location.host, location.port, socks, request.as_bytes()) {
+        Ok(bytes) => Response::Success(Content {
+            url: Some(url.to_string()),
+            mime: String::from("text/plain"),
+            bytes: format_ph(&String::from_utf8_lossy(&bytes)).into_bytes(),
+        }),
+        Err(e) => Response::Error(e),
+    }
+}
+
+/// Formats the response of a ph server as text. Each matching entry is given
+/// as `field: value` lines, with a blank line between entries, while errors
+/// and other messages are shown as sent. The final replies to the `query`
+/// and `quit` commands are left out.
+fn format_ph(response: &str) -> String {
+    let mut text = String::new();
+    let mut entry = None;
+    for line in response.lines() {
+        let Some((code, rest)) = line.split_once(':') else {
+            continue;
+        };
+        let Ok(code) = code.trim_start_matches('-').parse::<u16>() else {
+            continue;
+        };
+        match rest.split_once(':') {
+            Some((index, field)) if line.starts_with('-') => {
+                let (name, value) = field.split_once(':').unwrap_or(("", field));
+                if matches!(entry, Some(e) if e != index) {
+                    text.push('\n');
+                }
+                entry = Some(index);
+                let _ = writeln!(text, "{}: {}", name.trim(), value.trim());
+            }
+            _ if (200..300).contains(&code) => {}
+            _ => {
+                let _ = writeln!(text, "{}", rest.trim());
+            }
+        }
+    }
+    text
+}
+
+/// Sends a Gopher+ request for the item at `url`, with `command` following
+/// the selector, eg `!` for attributes or `+text/plain` for a view. Returns the
+/// response with the Gopher+ header removed.
+pub(crate) fn gopher_plus(
+    url: &str,
+    command: &str,
+    socks: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    let location = Location::from_url(url).ok_or_else(|| format!("Invalid gopher url: {url}"))?;
+    let request = format!("{}\t{command}\r\n", location.selector);
+    let data = exchange(&location.host, location.port, socks, request.as_bytes())?;
+    gopherplus::strip_header(&data).map(<[u8]>::to_vec)
+}
+
+/// Requests a finger url
+pub(crate) fn finger(url: &Url, socks: Option<&str>) -> Response {
+    if socks.is_some() {
+        return finger_via(url, socks);
+    }
+    match finger::request(url) {
+        Ok(content) => Response::Success(content),
+        Err(e) => Response::Error(format!("{e:?}")),
+    }
+}
+
+/// Performs a finger request over our own connection
+fn finger_via(url: &Url, socks: Option<&str>) -> Response {
+    let Some(host) = url.host_str() else {
+        return Response::Error(format!("Invalid url: {url}"));
+    };
+    let user = url.path().trim_start_matches('/');
+    let user = urlencoding::decode(user).map_or(user.to_string(), |u| u.to_string());
+    match exchange(
+        host,
+        url.port().unwrap_or(79),
+        socks,
+        format!("{user}\r\n").as_bytes(),
+    ) {
+        Ok(bytes) => Response::Success(Content {
+            url: Some(url.to_string()),
+            mime: String::from("text/plain"),
+            bytes,
+        }),
+        Err(e) => Response::Error(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{net::TcpListener, thread},
+    };
+
+    /// The host, port and request received by [`socks_stand_in`]
+    type Received = (String, u16, Vec<u8>);
+
+    /// Runs a SOCKS5 server for a single connection, which only accepts
+    /// hostnames to be resolved by the proxy and answers the first line sent
+    /// through it with `response`. Returns the address of the proxy.
+    fn socks_stand_in(response: &'static [u8]) -> (String, thread::JoinHandle<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut greeting = [0; 2];
+            stream.read_exact(&mut greeting).unwrap();
+            let mut methods = vec![0; usize::from(greeting[1])];
+            stream.read_exact(&mut methods).unwrap();
+            assert!(methods.contains(&0));
+            stream.write_all(&[5, 0]).unwrap();
+            let mut connect = [0; 4];
+            stream.read_exact(&mut connect).unwrap();
+            assert_eq!(connect[..3], [5, 1, 0]);
+            assert_eq!(connect[3], 3, "the address type should be a domain name");
+            let mut len = [0; 1];
+            stream.read_exact(&mut len).unwrap();
+            let mut host = vec![0; usize::from(len[0])];
+            stream.read_exact(&mut host).unwrap();
+            let mut port = [0; 2];
+            stream.read_exact(&mut port).unwrap();
+            stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+            let mut request = vec![];
+            let mut byte = [0; 1];
+            while !request.ends_with(b"\r\n") {
+                stream.read_exact(&mut byte).unwrap();
+                request.push(byte[0]);
+            }
+            stream.write_all(response).unwrap();
+            (
+                String::from_utf8(host).unwrap(),
+                u16::from_be_bytes(port),
+                request,
+            )
+        });
+        (address, server)
+    }
+
+    #[test]
+    fn socks_resolves_hostnames_remotely() {
+        let (proxy, server) = socks_stand_in(b"Login: user\r\n");
+        let url = Url::parse("finger://example.onion/user").unwrap();
+        let response = finger(&url, Some(&proxy));
+        let (host, port, request) = server.join().unwrap();
+        assert_eq!(host, "example.onion");
+        assert_eq!(port, 79);
+        assert_eq!(request, b"user\r\n");
+        let Response::Success(content) = response else {
+            panic!("the request should succeed");
+        };
+        assert_eq!(content.bytes, b"Login: user\r\n");
+    }
+
+    #[test]
+    fn socks_gopher_search() {
+        let (proxy, server) = socks_stand_in(b"iNo results\t\texample.onion\t70\r\n.\r\n");
+        let url = Url::parse("gopher://example.onion:7070/7/find%3Fdb=1?gemini%20feeds").unwrap();
+        let response = gopher(&url, Some(&proxy));
+        let (host, port, request) = server.join().unwrap();
+        assert_eq!(host, "example.onion");
+        assert_eq!(port, 7070);
+        assert_eq!(request, b"/find?db=1\tgemini feeds\r\n");
+        let Response::Success(content) = response else {
+            panic!("the request should succeed");
+        };
+        assert_eq!(content.mime, "text/plain");
+    }
+
+    #[test]
+    fn cso_lookup() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut byte = [0; 1];
+            while !request.ends_with(b"quit\r\n") {
+                stream.read_exact(&mut byte).unwrap();
+                request.push(byte[0]);
+            }
+            stream
+                .write_all(
+                    b"102:There was 1 match to your request.\r\n\
+                      -200:1:      name: Doe, Jane\r\n\
+                      -200:1:     email: jane@example.org\r\n\
+                      200:Ok.\r\n\
+                      200:Bye!\r\n",
+                )
+                .unwrap();
+            request
+        });
+        let url = Url::parse(&format!("gopher://127.0.0.1:{port}/2?name=jane%20doe")).unwrap();
+        let response = gopher(&url, None);
+        assert_eq!(server.join().unwrap(), b"query name=jane doe\r\nquit\r\n");
+        let Response::Success(content) = response else {
+            panic!("the lookup should succeed");
+        };
+        assert_eq!(
+            String::from_utf8(content.bytes).unwrap(),
+            "There was 1 match to your request.\nname: Doe, Jane\nemail: jane@example.org\n"
+        );
+    }
+
+    #[test]
+    fn ph_responses() {
+        let response = "-200:1:name:A\n-200:1:phone:1\n-200:2:name:B\n200:Ok.\n";
+        assert_eq!(format_ph(response), "name: A\nphone: 1\n\nname: B\n");
+        assert_eq!(
+            format_ph("501:No matches to your query.\r\n200:Bye!\r\n"),
+            "No matches to your query.\n"
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Replaces the contents of a file by writing them to a temporary file beside
+/// it, which is then renamed over the original. The original file is left
+/// untouched if writing fails part way through.
+pub(crate) fn write(path: &Path, contents: &str) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let mut temp = OsString::from(".");
+    temp.push(name);
+    temp.push(".tmp");
+    let temp = path.with_file_name(temp);
+    let result = fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_contents() {
+        let dir = std::env::temp_dir().join(format!("gemview-savefile-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bookmarks.ini");
+        fs::write(&path, "old contents, which are longer").unwrap();
+        write(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(!dir.join(".bookmarks.ini.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
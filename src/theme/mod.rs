@@ -0,0 +1,211 @@
+/// The colors used to render pages. Colors may be given in any form accepted
+/// by CSS, such as "#3465a4". They are applied through a stylesheet with a
+/// lower priority than application stylesheets, so the host application may
+/// still restyle the page through the CSS classes of its elements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// The page background
+    pub background: String,
+    /// Paragraphs and list items
+    pub text: String,
+    /// All three levels of headings
+    pub heading: String,
+    /// The text of links
+    pub link: String,
+    /// Blockquotes
+    pub quote: String,
+    /// Preformatted blocks, plain text documents and gopher menus
+    pub preformatted: String,
+    /// The icon in front of links to gemini, spartan and local pages
+    pub gemini_icon: String,
+    /// The icon in front of gopher links
+    pub gopher_icon: String,
+    /// The icon in front of finger links
+    pub finger_icon: String,
+    /// The icon in front of `data:` links
+    pub data_icon: String,
+    /// The icon in front of http and https links
+    pub web_icon: String,
+    /// The icon in front of any other link, such as `mailto:`
+    pub other_icon: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+impl Theme {
+    #[must_use]
+    /// Dark text on a light background
+    pub fn light() -> Self {
+        Self {
+            background: String::from("#ffffff"),
+            text: String::from("#2e3436"),
+            heading: String::from("#1a5fb4"),
+            link: String::from("#1c71d8"),
+            quote: String::from("#5e5c64"),
+            preformatted: String::from("#241f31"),
+            gemini_icon: String::from("#1a5fb4"),
+            gopher_icon: String::from("#26a269"),
+            finger_icon: String::from("#2190a4"),
+            data_icon: String::from("#9141ac"),
+            web_icon: String::from("#c01c28"),
+            other_icon: String::from("#c64600"),
+        }
+    }
+
+    #[must_use]
+    /// Light text on a dark background
+    pub fn dark() -> Self {
+        Self {
+            background: String::from("#1e1e1e"),
+            text: String::from("#deddda"),
+            heading: String::from("#99c1f1"),
+            link: String::from("#78aeed"),
+            quote: String::from("#c0bfbc"),
+            preformatted: String::from("#f6f5f4"),
+            gemini_icon: String::from("#62a0ea"),
+            gopher_icon: String::from("#57e389"),
+            finger_icon: String::from("#93ddc2"),
+            data_icon: String::from("#dc8add"),
+            web_icon: String::from("#f66151"),
+            other_icon: String::from("#f8e45c"),
+        }
+    }
+
+    #[must_use]
+    /// Gets the color of the icon shown in front of links with the given
+    /// scheme
+    pub fn icon_color(&self, scheme: &str) -> &str {
+        match scheme {
+            "gemini" | "spartan" | "file" => &self.gemini_icon,
+            "gopher" => &self.gopher_icon,
+            "finger" => &self.finger_icon,
+            "data" => &self.data_icon,
+            "http" | "https" => &self.web_icon,
+            _ => &self.other_icon,
+        }
+    }
+
+    #[must_use]
+    /// Returns `true` if the background is darker than mid grey. Backgrounds
+    /// which are not given as "#rrggbb" are assumed to be light.
+    pub fn is_dark(&self) -> bool {
+        let Some(hex) = self.background.strip_prefix('#').filter(|h| h.len() == 6) else {
+            return false;
+        };
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .map_or(0.0, f64::from)
+        };
+        let luma = 0.299 * channel(0) + 0.587 * channel(2) + 0.114 * channel(4);
+        luma < 128.0
+    }
+
+    /// Generates the stylesheet which colors the page background, any text
+    /// not covered by a tag and the widgets added to the page
+    pub(crate) fn to_css(&self) -> String {
+        let mut css = format!(
+            "text {{ background-color: {}; color: {}; }}\n\
+             .blockquote {{ color: {}; }}\n\
+             .preformatted {{ color: {}; }}\n\
+             .link label > link {{ color: {}; }}\n\
+             .link-hint {{ background-color: {}; color: {}; font-weight: bold; }}\n",
+            self.background,
+            self.text,
+            self.quote,
+            self.preformatted,
+            self.link,
+            self.link,
+            self.background
+        );
+        for scheme in ["gemini", "gopher", "finger", "data", "http", "other"] {
+            css.push_str(&format!(
+                ".{} {{ color: {}; }}\n",
+                icon_class(scheme),
+                self.icon_color(scheme)
+            ));
+        }
+        css
+    }
+}
+
+/// Gets the CSS class of the icon shown in front of links with the given
+/// scheme, which is colored as described for [`Theme::icon_color`]
+pub(crate) fn icon_class(scheme: &str) -> &'static str {
+    match scheme {
+        "gemini" | "spartan" | "file" => "gemini-icon",
+        "gopher" => "gopher-icon",
+        "finger" => "finger-icon",
+        "data" => "data-icon",
+        "http" | "https" => "web-icon",
+        _ => "other-icon",
+    }
+}
+
+/// The accent colors given to an individual capsule or gopher hole, which
+/// replace those of the [`Theme`] while browsing that host
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    /// All three levels of headings
+    pub heading: String,
+    /// The text of links
+    pub link: String,
+    /// Blockquotes
+    pub quote: String,
+}
+
+impl Palette {
+    #[must_use]
+    /// Derives a palette from a hash of the hostname, so that every host gets
+    /// its own stable set of colors. The colors are darker for light themes
+    /// and lighter for dark themes so that they remain readable.
+    pub fn from_host(host: &str, dark: bool) -> Self {
+        // FNV-1a, which unlike the std hasher is stable between releases
+        let hash = host.bytes().fold(0x811c_9dc5_u32, |h, b| {
+            (h ^ u32::from(b)).wrapping_mul(0x0100_0193)
+        });
+        let hue = f64::from((hash ^ (hash >> 16)) % 360);
+        let lightness = if dark { 0.72 } else { 0.35 };
+        Self {
+            heading: hsl_to_hex(hue, 0.65, lightness),
+            link: hsl_to_hex(hue + 40.0, 0.75, lightness),
+            quote: hsl_to_hex(hue - 40.0, 0.3, lightness),
+        }
+    }
+
+    /// Replaces the accent colors of a theme with those of this palette
+    pub(crate) fn apply(&self, theme: &mut Theme) {
+        theme.heading.clone_from(&self.heading);
+        theme.link.clone_from(&self.link);
+        theme.quote.clone_from(&self.quote);
+    }
+}
+
+/// Converts a hue in degrees along with a saturation and lightness between 0
+/// and 1 into a "#rrggbb" color
+fn hsl_to_hex(hue: f64, saturation: f64, lightness: f64) -> String {
+    let hue = hue.rem_euclid(360.0) / 60.0;
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let second = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+    let (red, green, blue) = match hue {
+        h if h < 1.0 => (chroma, second, 0.0),
+        h if h < 2.0 => (second, chroma, 0.0),
+        h if h < 3.0 => (0.0, chroma, second),
+        h if h < 4.0 => (0.0, second, chroma),
+        h if h < 5.0 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let offset = lightness - chroma / 2.0;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let channel = |c: f64| ((c + offset) * 255.0).round().clamp(0.0, 255.0) as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(red),
+        channel(green),
+        channel(blue)
+    )
+}
@@ -9,7 +9,6 @@ impl ToLabel for gopher::parser::Link {
     fn to_label(&self, font: &FontDescription) -> Label {
         gtk::Label::builder()
             .use_markup(true)
-            .tooltip_text(self.to_string())
             .label(self.to_markup(font))
             .cursor(&Cursor::from_name("pointer", None).unwrap())
             .build()
@@ -21,7 +20,6 @@ impl ToLabel for gopher::parser::ExternLink {
         gtk::Label::builder()
             .selectable(true)
             .use_markup(true)
-            .tooltip_text(&self.url)
             .label(self.to_markup(font))
             .cursor(&Cursor::from_name("pointer", None).unwrap())
             .build()
@@ -36,7 +34,7 @@ impl ToMarkup for gopher::parser::ExternLink {
     fn to_markup(&self, font: &FontDescription) -> String {
         let url = self.url.replace('&', "&amp;");
         format!(
-            "<span color=\"#ff0000\"> 🌐  </span><span font=\"{font}\"><a href=\"{}\">{}</a></span>",
+            "<span font=\"{font}\"><a href=\"{}\">{}</a></span>",
             &url,
             glib::markup_escape_text(&self.display)
         )
@@ -44,11 +42,13 @@ impl ToMarkup for gopher::parser::ExternLink {
 }
 
 impl ToMarkup for gopher::parser::Link {
-    /// Generates Pango markup from a Gopher link
+    /// Generates Pango markup from a Gopher link. The icon representing the
+    /// item type is added separately when the link is inserted.
     fn to_markup(&self, font: &FontDescription) -> String {
+        let url = self.to_string();
         format!(
-            "<span color=\"#00ff00\"> 🕳️  </span><span font=\"{font}\"><a href=\"{}\">{}</a></span>",
-            &self.to_string().replace(' ', "%20").replace('&', "&amp;"),
+            "<span font=\"{font}\"><a href=\"{}\">{}</a></span>",
+            &url.replace(' ', "%20").replace('&', "&amp;"),
             glib::markup_escape_text(&self.display)
         )
     }
@@ -1,12 +1,25 @@
 use gtk::{
     glib::{self, subclass::InitializingObject},
     subclass::prelude::*,
-    CompositeTemplate,
+    CompositeTemplate, TemplateChild,
 };
 
 #[derive(CompositeTemplate, Default)]
 #[template(file = "upload_widget.ui")]
-pub struct UploadWidget {}
+pub struct UploadWidget {
+    #[template_child]
+    pub switcher: TemplateChild<gtk::StackSwitcher>,
+    #[template_child]
+    pub file_page: TemplateChild<gtk::StackPage>,
+    #[template_child]
+    pub editor: TemplateChild<gtk::TextView>,
+    #[template_child]
+    pub editor_counter: TemplateChild<gtk::Label>,
+    #[template_child]
+    pub editor_cancel: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub editor_upload: TemplateChild<gtk::Button>,
+}
 
 #[glib::object_subclass]
 impl ObjectSubclass for UploadWidget {
@@ -1,6 +1,10 @@
 mod imp;
 
-use gtk::glib::{self, Object};
+use gtk::{
+    glib::{self, Object},
+    prelude::*,
+    subclass::prelude::*,
+};
 
 glib::wrapper! {
     pub struct UploadWidget(ObjectSubclass<imp::UploadWidget>)
@@ -21,4 +25,38 @@ impl UploadWidget {
             .property("orientation", gtk::Orientation::Vertical)
             .build()
     }
+
+    /// Switches the widget to a plain text editor for answering an input
+    /// prompt. The file chooser is hidden and a counter displaying the
+    /// remaining byte budget is shown.
+    pub fn set_input_mode(&self) {
+        let imp = self.imp();
+        imp.switcher.set_visible(false);
+        imp.file_page.set_visible(false);
+        imp.editor_counter.set_visible(true);
+        imp.editor_upload.set_label("Submit");
+    }
+
+    pub fn editor(&self) -> gtk::TextView {
+        self.imp().editor.get()
+    }
+
+    /// Returns the full text of the editor
+    pub fn text(&self) -> String {
+        let buf = self.imp().editor.buffer();
+        let (start, end) = buf.bounds();
+        buf.text(&start, &end, false).to_string()
+    }
+
+    pub fn counter(&self) -> gtk::Label {
+        self.imp().editor_counter.get()
+    }
+
+    pub fn submit_button(&self) -> gtk::Button {
+        self.imp().editor_upload.get()
+    }
+
+    pub fn cancel_button(&self) -> gtk::Button {
+        self.imp().editor_cancel.get()
+    }
 }